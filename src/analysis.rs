@@ -0,0 +1,250 @@
+//!
+//! Post-game analysis of recorded games
+//!
+
+use crate::engine::Engine;
+use crate::game::Game;
+use crate::game_enums::{Cell, Turn};
+use crate::recording::{Move, Recording};
+
+/// Engine verdict on a single recorded move
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveAnalysis {
+    pub played: Move,
+    pub player: Turn,
+    /// SOSs completed by the played move
+    pub scored: u32,
+    /// The engine's preferred move in the same position
+    pub best: Move,
+    /// Values of the played and best moves for the player who moved
+    pub played_value: i32,
+    pub best_value: i32,
+    /// An SOS could have been completed but the played move completed fewer
+    pub missed_sos: bool,
+    /// The played move handed the opponent an SOS although a safe move existed
+    pub blunder: bool
+}
+
+impl MoveAnalysis {
+    /// Whether the played move was as good as the engine's choice
+    pub fn is_best(&self) -> bool {
+        self.played_value >= self.best_value
+    }
+
+    /// Short remark on the move on a `board_size` board, empty if there is nothing to say
+    pub fn comment(&self, board_size: usize) -> String {
+        if self.played.cell == Cell::Empty {
+            return "turn forfeited".to_string();
+        }
+        let mut remarks = Vec::new();
+        if self.blunder {
+            remarks.push("blunder".to_string());
+        }
+        if self.missed_sos {
+            remarks.push("missed SOS".to_string());
+        }
+        if !self.is_best() {
+            remarks.push(format!("best was {}", self.best.to_notation(board_size)));
+        }
+        remarks.join(", ")
+    }
+}
+
+/// Totals for one side of the game
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerSummary {
+    pub moves: usize,
+    pub sos_made: u32,
+    pub best_moves: usize,
+    pub missed_sos: usize,
+    pub blunders: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub board_size: usize,
+    pub moves: Vec<MoveAnalysis>,
+    pub left: PlayerSummary,
+    pub right: PlayerSummary
+}
+
+impl Analysis {
+    /// Replay a recording through `Game`, judging every move with `engine`
+    ///
+    /// Analysis stops at the first move the game would not have allowed.
+    pub fn new(recording: &Recording, engine: &Engine) -> Self {
+        let mut game = Game::at_start(recording);
+
+        let mut analysis = Self {
+            board_size: recording.board_size,
+            moves: Vec::new(),
+            left: PlayerSummary::default(),
+            right: PlayerSummary::default()
+        };
+
+        for played in &recording.moves {
+            let (best, best_value) = match engine.search(&game) {
+                Some(result) => result,
+                // The recording carries on past the end of the game
+                None => break
+            };
+            if played.cell != Cell::Empty && !game.legal_moves().contains(played) {
+                break;
+            }
+            // A turn given up on time is noted but not judged
+            if played.cell == Cell::Empty {
+                analysis.moves.push(MoveAnalysis {
+                    played: played.clone(),
+                    player: game.turn,
+                    scored: 0,
+                    best,
+                    played_value: best_value,
+                    best_value,
+                    missed_sos: false,
+                    blunder: false
+                });
+                game.make_move(played.cell, played.row, played.col);
+                continue;
+            }
+            let legal = game.legal_moves();
+            let most_available = legal.iter()
+                .map(|m| game.sos_if_placed(m.cell, m.row, m.col))
+                .max()
+                .unwrap_or(0);
+            let safe_available = legal.iter()
+                .any(|m| !game.gives_away_sos(m.cell, m.row, m.col));
+
+            let scored = game.sos_if_placed(played.cell, played.row, played.col);
+            let move_analysis = MoveAnalysis {
+                played: played.clone(),
+                player: game.turn,
+                scored,
+                played_value: engine.move_value(&game, played),
+                best,
+                best_value,
+                missed_sos: scored < most_available,
+                blunder: scored == 0 && safe_available
+                    && game.gives_away_sos(played.cell, played.row, played.col)
+            };
+
+            let summary = match game.turn {
+                Turn::Left => &mut analysis.left,
+                Turn::Right => &mut analysis.right
+            };
+            summary.moves += 1;
+            summary.sos_made += scored;
+            summary.best_moves += move_analysis.is_best() as usize;
+            summary.missed_sos += move_analysis.missed_sos as usize;
+            summary.blunders += move_analysis.blunder as usize;
+
+            analysis.moves.push(move_analysis);
+            game.make_move(played.cell, played.row, played.col);
+        }
+        analysis
+    }
+
+    /// Human-readable report with one line per move followed by a summary per player
+    pub fn as_string(&self) -> String {
+        let mut string = String::new();
+        for (i, m) in self.moves.iter().enumerate() {
+            string += &format!("{:>3}. {} {}", i + 1, player_name(m.player), m.played.to_notation(self.board_size));
+            if m.scored > 0 {
+                string += &format!(" (+{})", m.scored);
+            }
+            let comment = m.comment(self.board_size);
+            if !comment.is_empty() {
+                string += " ";
+                string += &comment;
+            }
+            string += "\n";
+        }
+        for (turn, summary) in [(Turn::Left, &self.left), (Turn::Right, &self.right)] {
+            string += &format!(
+                "{}: {} moves, {} SOS, {} best, {} missed SOS, {} blunders\n",
+                player_name(turn), summary.moves, summary.sos_made,
+                summary.best_moves, summary.missed_sos, summary.blunders
+            );
+        }
+        string
+    }
+}
+
+fn player_name(turn: Turn) -> &'static str {
+    match turn {
+        Turn::Left => "Player 1",
+        Turn::Right => "Player 2"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Cell, Mode};
+
+    #[test]
+    fn flags_move_that_gives_away_sos() {
+        let mut recording = Recording::new(Mode::Classic, 5);
+        recording.add_move(Cell::S, 2, 2);
+        recording.add_move(Cell::O, 2, 3);
+        let analysis = Analysis::new(&recording, &Engine::new(2));
+
+        assert!(!analysis.moves[0].blunder);
+        assert!(analysis.moves[1].blunder);
+        assert_eq!(analysis.right.blunders, 1);
+    }
+
+    #[test]
+    fn flags_missed_sos() {
+        let mut recording = Recording::new(Mode::Classic, 5);
+        recording.add_move(Cell::S, 0, 0);
+        recording.add_move(Cell::S, 0, 2);
+        recording.add_move(Cell::S, 4, 4);
+        let analysis = Analysis::new(&recording, &Engine::new(1));
+
+        assert!(analysis.moves[2].missed_sos);
+        assert_eq!(analysis.moves[2].best, Move { cell: Cell::O, row: 0, col: 1 });
+        assert_eq!(analysis.moves[2].comment(5), "missed SOS, best was O b5");
+        assert!(analysis.as_string().contains("3. Player 1 S e1 missed SOS, best was O b5"));
+        assert_eq!(analysis.left.missed_sos, 1);
+    }
+
+    #[test]
+    fn counts_sos_per_player() {
+        let mut recording = Recording::new(Mode::Classic, 5);
+        recording.add_move(Cell::S, 0, 0);
+        recording.add_move(Cell::S, 0, 2);
+        recording.add_move(Cell::O, 0, 1);
+        let analysis = Analysis::new(&recording, &Engine::new(1));
+
+        assert_eq!(analysis.left.sos_made, 1);
+        assert_eq!(analysis.left.moves, 2);
+        assert_eq!(analysis.right.moves, 1);
+    }
+
+    #[test]
+    fn stops_at_end_of_game() {
+        let mut recording = Recording::new(Mode::Simple, 3);
+        recording.add_move(Cell::S, 0, 0);
+        recording.add_move(Cell::S, 0, 2);
+        recording.add_move(Cell::O, 0, 1);
+        recording.add_move(Cell::S, 2, 2);
+        let analysis = Analysis::new(&recording, &Engine::new(1));
+
+        assert_eq!(analysis.moves.len(), 3);
+    }
+
+    #[test]
+    fn stops_at_moves_the_game_would_not_allow() {
+        let mut recording = Recording::new(Mode::Classic, 5);
+        recording.add_move(Cell::S, 9, 1);
+        recording.add_move(Cell::O, 0, 0);
+        assert!(Analysis::new(&recording, &Engine::new(1)).moves.is_empty());
+
+        let mut recording = Recording::new(Mode::Classic, 5);
+        recording.add_move(Cell::S, 2, 2);
+        recording.add_move(Cell::O, 2, 2);
+        let analysis = Analysis::new(&recording, &Engine::new(1));
+        assert_eq!(analysis.moves.len(), 1);
+        assert_eq!(analysis.as_string().lines().count(), 3);
+    }
+}
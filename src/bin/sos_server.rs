@@ -0,0 +1,15 @@
+use std::net::TcpListener;
+use std::process;
+use sos_game::server::{Server, DEFAULT_SERVER_PORT};
+
+/// Headless SOS server: `sos_server [address]`
+fn main() {
+    let address = std::env::args().nth(1)
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_SERVER_PORT));
+    let listener = TcpListener::bind(&address).unwrap_or_else(|e| {
+        eprintln!("Could not listen on {}: {}", address, e);
+        process::exit(1);
+    });
+    println!("Serving SOS games on {}", address);
+    Server::new().serve(listener);
+}
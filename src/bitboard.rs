@@ -0,0 +1,245 @@
+//!
+//! Compact board representation for fast move application and SOS counting
+//!
+//! Cells are numbered row by row from the top left, and each letter is a bit in its own mask.
+//! Every possible SOS line is worked out once per board size, so counting the SOSs a letter
+//! completes is a handful of mask tests.
+//!
+
+use std::sync::OnceLock;
+use crate::game_enums::Cell;
+use crate::symmetry::Symmetry;
+
+/// Smallest board with room for an SOS
+pub const MIN_BOARD_SIZE: usize = 3;
+
+/// Largest board whose cells fit in the masks
+pub const MAX_BOARD_SIZE: usize = 11;
+
+/// The SOS lines through each cell of one board size
+struct LineTable {
+    /// For each cell, the two end cells of every line with that cell in the middle
+    middle: Vec<Vec<u128>>,
+    /// For each cell, the middle and far end of every line starting at that cell
+    end: Vec<Vec<(u128, u128)>>
+}
+
+impl LineTable {
+    fn new(size: usize) -> Self {
+        let cells = size * size;
+        let mut table = Self { middle: vec![Vec::new(); cells], end: vec![Vec::new(); cells] };
+        for row in 0..size {
+            for col in 0..size {
+                for (row_step, col_step) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    let first = (row as isize - row_step, col as isize - col_step);
+                    let last = (row as isize + row_step, col as isize + col_step);
+                    let on_board = |(r, c): (isize, isize)| r >= 0 && c >= 0 && r < size as isize && c < size as isize;
+                    if !on_board(first) || !on_board(last) {
+                        continue;
+                    }
+                    let first = first.0 as usize * size + first.1 as usize;
+                    let middle = row * size + col;
+                    let last = last.0 as usize * size + last.1 as usize;
+                    table.middle[middle].push(bit(first) | bit(last));
+                    table.end[first].push((bit(middle), bit(last)));
+                    table.end[last].push((bit(middle), bit(first)));
+                }
+            }
+        }
+        table
+    }
+}
+
+/// Line tables for every supported size, built the first time they are needed
+fn line_table(size: usize) -> &'static LineTable {
+    static TABLES: OnceLock<Vec<LineTable>> = OnceLock::new();
+    &TABLES.get_or_init(|| (0..=MAX_BOARD_SIZE).map(LineTable::new).collect())[size]
+}
+
+fn bit(index: usize) -> u128 {
+    1 << index
+}
+
+/// A square board of up to `MAX_BOARD_SIZE` cells a side, as one mask per letter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bitboard {
+    size: usize,
+    s: u128,
+    o: u128
+}
+
+impl Bitboard {
+    pub fn new(size: usize) -> Self {
+        assert!(size <= MAX_BOARD_SIZE, "board size {} is larger than {}", size, MAX_BOARD_SIZE);
+        Self { size, s: 0, o: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Cells holding an S
+    pub fn s_mask(&self) -> u128 {
+        self.s
+    }
+
+    /// Cells holding an O
+    pub fn o_mask(&self) -> u128 {
+        self.o
+    }
+
+    /// Cells with no letter
+    pub fn empty_mask(&self) -> u128 {
+        let all = if self.size == 0 { 0 } else { u128::MAX >> (128 - self.size * self.size) };
+        all & !(self.s | self.o)
+    }
+
+    pub fn index(&self, row: usize, col: usize) -> usize {
+        row * self.size + col
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Cell {
+        let cell = bit(self.index(row, col));
+        if self.s & cell != 0 {
+            Cell::S
+        } else if self.o & cell != 0 {
+            Cell::O
+        } else {
+            Cell::Empty
+        }
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, cell: Cell) {
+        let index = bit(self.index(row, col));
+        self.s &= !index;
+        self.o &= !index;
+        match cell {
+            Cell::S => self.s |= index,
+            Cell::O => self.o |= index,
+            Cell::Empty => ()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.s = 0;
+        self.o = 0;
+    }
+
+    /// Number of cells with a letter
+    pub fn filled(&self) -> usize {
+        (self.s | self.o).count_ones() as usize
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.filled() == self.size * self.size
+    }
+
+    /// Number of SOSs that `input` at (row, col) takes part in, whatever is in that cell now
+    pub fn sos_made(&self, input: Cell, row: usize, col: usize) -> u32 {
+        let index = self.index(row, col);
+        let table = line_table(self.size);
+        match input {
+            Cell::O => table.middle[index].iter().filter(|ends| self.s & **ends == **ends).count() as u32,
+            Cell::S => table.end[index].iter()
+                .filter(|(middle, far)| self.o & middle != 0 && self.s & far != 0)
+                .count() as u32,
+            Cell::Empty => 0
+        }
+    }
+
+    /// The board turned or mirrored by `symmetry`
+    pub fn transformed(&self, symmetry: Symmetry) -> Self {
+        let map = symmetry.cell_map(self.size);
+        let image = |mut mask: u128| {
+            let mut moved = 0;
+            while mask != 0 {
+                moved |= bit(map[mask.trailing_zeros() as usize]);
+                mask &= mask - 1;
+            }
+            moved
+        };
+        Self { size: self.size, s: image(self.s), o: image(self.o) }
+    }
+
+    /// The image of the board that every symmetric board shares, and the symmetry that gives it
+    ///
+    /// The image with the smallest S mask, then O mask, is the canonical one
+    pub fn canonical(&self) -> (Self, Symmetry) {
+        Symmetry::ALL.iter()
+            .map(|symmetry| (self.transformed(*symmetry), *symmetry))
+            .min_by_key(|(board, _)| (board.s, board.o))
+            .unwrap()
+    }
+
+    /// Board as rows of cells from the top
+    pub fn rows(&self) -> Vec<Vec<Cell>> {
+        (0..self.size).map(|row| (0..self.size).map(|col| self.get(row, col)).collect()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_and_get_cells() {
+        let mut board = Bitboard::new(4);
+        board.set(1, 2, Cell::S);
+        board.set(3, 3, Cell::O);
+        assert_eq!(board.get(1, 2), Cell::S);
+        assert_eq!(board.get(3, 3), Cell::O);
+        assert_eq!(board.get(0, 0), Cell::Empty);
+        assert_eq!(board.filled(), 2);
+        board.set(1, 2, Cell::O);
+        assert_eq!(board.get(1, 2), Cell::O);
+        assert_eq!(board.empty_mask().count_ones(), 14);
+    }
+
+    #[test]
+    fn counts_sos_in_every_direction() {
+        // S in every cell around the centre of a 3 by 3 board
+        let mut board = Bitboard::new(3);
+        for row in 0..3 {
+            for col in 0..3 {
+                if (row, col) != (1, 1) {
+                    board.set(row, col, Cell::S);
+                }
+            }
+        }
+        assert_eq!(board.sos_made(Cell::O, 1, 1), 4);
+        assert_eq!(board.sos_made(Cell::S, 1, 1), 0);
+
+        let mut board = Bitboard::new(5);
+        for (row, col, cell) in [(0, 0, Cell::S), (1, 1, Cell::O), (2, 4, Cell::S), (2, 3, Cell::O),
+                                 (0, 2, Cell::S), (1, 2, Cell::O)] {
+            board.set(row, col, cell);
+        }
+        // Diagonal, row and column all meet at the centre
+        assert_eq!(board.sos_made(Cell::S, 2, 2), 3);
+        assert_eq!(board.sos_made(Cell::O, 0, 1), 1);
+        assert_eq!(board.sos_made(Cell::S, 4, 4), 0);
+    }
+
+    #[test]
+    fn largest_board_fits() {
+        let mut board = Bitboard::new(MAX_BOARD_SIZE);
+        board.set(MAX_BOARD_SIZE - 1, MAX_BOARD_SIZE - 1, Cell::S);
+        assert_eq!(board.empty_mask().count_ones() as usize, MAX_BOARD_SIZE * MAX_BOARD_SIZE - 1);
+        assert!(!board.is_full());
+    }
+
+    #[test]
+    fn symmetric_boards_share_a_canonical_form() {
+        let mut board = Bitboard::new(4);
+        board.set(0, 1, Cell::S);
+        board.set(2, 2, Cell::O);
+        let (canonical, symmetry) = board.canonical();
+        for other in Symmetry::ALL {
+            let image = board.transformed(other);
+            assert_eq!(image.filled(), 2);
+            assert_eq!(image.canonical().0, canonical);
+        }
+        assert_eq!(board.transformed(symmetry), canonical);
+        assert_eq!(canonical.transformed(symmetry.inverse()), board);
+    }
+}
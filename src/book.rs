@@ -0,0 +1,225 @@
+//!
+//! Opening book built from recorded games
+//!
+//! Every position in the first moves of a recording is stored in its canonical orientation, with
+//! the results of each move played from it, so games that only differ by a rotation or
+//! reflection of the board add up.
+//!
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use crate::game::Game;
+use crate::game_enums::{Cell, State};
+use crate::recording::{Move, Recording};
+use crate::symmetry::Symmetry;
+
+/// Moves from the start of each game that go into the book and that it is consulted for
+pub const BOOK_DEPTH: usize = 8;
+
+/// Results of the games in which a move was played, for the player who played it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32
+}
+
+impl MoveStats {
+    /// Share of the points won, counting a draw as half a win
+    pub fn score(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { (self.wins as f64 + self.draws as f64 / 2.0) / self.games as f64 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningBook {
+    /// Moves stored for this many moves from the start of a game
+    pub depth: usize,
+    /// Recordings added, not counting unfinished games
+    pub games: usize,
+    /// Moves played from each position, by canonical hash, in the canonical orientation
+    positions: HashMap<u64, Vec<(Move, MoveStats)>>
+}
+
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self::new(BOOK_DEPTH)
+    }
+}
+
+impl OpeningBook {
+    pub fn new(depth: usize) -> Self {
+        Self { depth, games: 0, positions: HashMap::new() }
+    }
+
+    /// Book of every `.sos` recording in `directory`, skipping files that cannot be read
+    pub fn from_directory(directory: &str, depth: usize) -> io::Result<Self> {
+        let mut book = Self::new(depth);
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "sos") {
+                if let Some(recording) = Recording::read_from_file(path.to_string_lossy().to_string()) {
+                    book.add_recording(&recording);
+                }
+            }
+        }
+        Ok(book)
+    }
+
+    /// Number of positions with at least one move
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Count the opening moves of a finished game
+    pub fn add_recording(&mut self, recording: &Recording) {
+        let result = Game::from_recording(recording).state;
+        if !result.is_over() {
+            return;
+        }
+        self.games += 1;
+
+        let mut game = Game::at_start(recording);
+        for m in recording.moves.iter().take(self.depth) {
+            if game.state != State::Playing {
+                break;
+            }
+            // Turns given up on time say nothing about the opening
+            if m.cell != Cell::Empty {
+                let (canonical, symmetry) = game.canonical();
+                let m_canonical = representative_move(&canonical, &symmetry.apply_move(game.get_board_size(), m));
+                let moves = self.positions.entry(canonical.hash()).or_default();
+                let index = match moves.iter().position(|(known, _)| *known == m_canonical) {
+                    Some(index) => index,
+                    None => {
+                        moves.push((m_canonical, MoveStats::default()));
+                        moves.len() - 1
+                    }
+                };
+                let stats = &mut moves[index].1;
+                stats.games += 1;
+                match result.winner() {
+                    None => stats.draws += 1,
+                    Some(winner) if winner == game.turn => stats.wins += 1,
+                    Some(_) => ()
+                }
+            }
+            game.make_move(m.cell, m.row, m.col);
+        }
+    }
+
+    /// Moves the book knows from this position, turned to match the board of `game`
+    pub fn moves(&self, game: &Game) -> Vec<(Move, MoveStats)> {
+        let (canonical, symmetry) = game.canonical();
+        let back = symmetry.inverse();
+        self.positions.get(&canonical.hash()).map_or(Vec::new(), |moves| {
+            moves.iter().map(|(m, stats)| (back.apply_move(game.get_board_size(), m), stats.clone())).collect()
+        })
+    }
+
+    /// The book move with the best results, or None once the game is past the opening or
+    /// the position is not in the book
+    ///
+    /// Ties go to the move played most often
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        if game.state != State::Playing || game.recording.moves.len() >= self.depth {
+            return None;
+        }
+        self.moves(game).into_iter()
+            .filter(|(m, _)| game.get_cell(m.col, m.row) == Ok(&Cell::Empty))
+            .max_by(|(_, a), (_, b)| a.score().total_cmp(&b.score()).then(a.games.cmp(&b.games)))
+            .map(|(m, _)| m)
+    }
+}
+
+/// The first, by row and column, of the moves that are alike because `game` looks the same
+/// after some symmetry, so alike moves are counted together
+fn representative_move(game: &Game, m: &Move) -> Move {
+    let board = game.bitboard();
+    Symmetry::ALL.iter()
+        .filter(|symmetry| board.transformed(**symmetry) == *board)
+        .map(|symmetry| symmetry.apply_move(board.size(), m))
+        .min_by_key(|image| (image.row, image.col))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Mode, Turn};
+
+    /// Simple game on a 3 by 3 board where the first player completes an SOS on their third move
+    fn left_win(moves: [(Cell, usize, usize); 5]) -> Recording {
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        for (cell, row, col) in moves {
+            g.make_move(cell, row, col);
+        }
+        assert_eq!(g.state, State::LeftWin);
+        g.recording
+    }
+
+    #[test]
+    fn symmetric_games_count_towards_the_same_move() {
+        let mut book = OpeningBook::new(BOOK_DEPTH);
+        book.add_recording(&left_win([(Cell::S, 0, 0), (Cell::S, 2, 2), (Cell::S, 0, 2),
+                                      (Cell::S, 2, 0), (Cell::O, 0, 1)]));
+        // The same game mirrored left to right
+        book.add_recording(&left_win([(Cell::S, 0, 2), (Cell::S, 2, 0), (Cell::S, 0, 0),
+                                      (Cell::S, 2, 2), (Cell::O, 0, 1)]));
+        assert_eq!(book.games, 2);
+
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        let moves = book.moves(&g);
+        // Corners are alike, so both games played the same book move
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].1, MoveStats { games: 2, wins: 2, draws: 0 });
+
+        // Seen from another corner, the book move is turned to match
+        g.make_move(Cell::S, 2, 2);
+        assert_eq!(book.best_move(&g), Some(Move { cell: Cell::S, row: 0, col: 0 }));
+    }
+
+    #[test]
+    fn better_results_are_preferred() {
+        let mut book = OpeningBook::new(BOOK_DEPTH);
+        book.add_recording(&left_win([(Cell::S, 1, 1), (Cell::S, 0, 0), (Cell::S, 2, 2),
+                                      (Cell::S, 2, 0), (Cell::O, 1, 0)]));
+        let mut lost = Game::new(Mode::Simple, 3);
+        lost.state = State::Playing;
+        lost.make_move(Cell::O, 1, 1);
+        lost.resign(Turn::Left);
+        book.add_recording(&lost.recording);
+
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        assert_eq!(book.best_move(&g), Some(Move { cell: Cell::S, row: 1, col: 1 }));
+    }
+
+    #[test]
+    fn unfinished_games_and_late_moves_are_left_out() {
+        let mut book = OpeningBook::new(1);
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 1, 1);
+        book.add_recording(&g.recording);
+        assert!(book.is_empty());
+
+        g.resign(Turn::Right);
+        book.add_recording(&g.recording);
+        assert_eq!(book.len(), 1);
+
+        let mut next = Game::new(Mode::Classic, 3);
+        next.state = State::Playing;
+        assert_eq!(book.best_move(&next), Some(Move { cell: Cell::S, row: 1, col: 1 }));
+        // Past the book's depth it has nothing to say
+        next.make_move(Cell::O, 0, 0);
+        assert_eq!(book.best_move(&next), None);
+    }
+}
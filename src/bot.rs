@@ -0,0 +1,257 @@
+//!
+//! Computer players running as separate programs
+//!
+//! The bot reads commands on stdin and answers on stdout, one per line:
+//!
+//! ```text
+//! sos <version>                          -> sosok        (any "id ..." lines before it are ignored)
+//! isready                                -> readyok
+//! position <rows> <L|R> <left> <right> <C|S>
+//! go                                     -> bestmove <S|O> <row> <col>
+//! quit
+//! ```
+//!
+//! Each `go` follows an `isready`. A bot answers `readyok` once it has answered everything sent
+//! before, so a late `bestmove` for a position already given up on is skipped rather than played.
+//!
+//! `rows` lists the board from the top, rows separated by `/`, with `-` for an empty cell,
+//! e.g. `S-O/---/--S`. The second field is the player on turn, followed by both scores and the mode.
+//! This is the position notation of `Game::to_notation`.
+//!
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::game::Game;
+use crate::game_enums::{Cell, State};
+use crate::notation::parse_cell;
+use crate::recording::Move;
+
+/// Bumped whenever the meaning of a bot command changes
+pub const BOT_PROTOCOL_VERSION: u32 = 2;
+/// How long a bot may think before its move is given up on
+pub const BOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running bot process
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// When the unanswered `go` was sent
+    asked: Option<Instant>,
+    /// `isready`s sent without a `readyok` back; a `bestmove` before the last one is stale
+    unready: usize
+}
+
+impl ExternalBot {
+    /// Start the bot with `command`, split on whitespace into a program and its arguments
+    pub fn launch(command: &str) -> std::io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no bot command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        // Reading on another thread lets us give up on a bot that never answers
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() { break },
+                    Err(..) => break
+                }
+            }
+        });
+
+        let mut bot = Self { child, stdin, lines, asked: None, unready: 0 };
+        bot.send(&format!("sos {}", BOT_PROTOCOL_VERSION))?;
+        match bot.wait_for(|line| line == "sosok") {
+            Some(_) => Ok(bot),
+            None => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "bot did not answer sos"))
+        }
+    }
+
+    /// Ask the bot for its move, or None if it fails to give a legal one in time
+    pub fn best_move(&mut self, game: &Game) -> Option<Move> {
+        self.go(game).ok()?;
+        while self.unready > 0 {
+            self.wait_for(|line| line == "readyok")?;
+            self.unready -= 1;
+        }
+        let line = self.wait_for(|line| line.starts_with("bestmove"))?;
+        self.asked = None;
+        legal_move(game, &line)
+    }
+
+    /// Send the position and ask for a move, to be collected with `poll_move`
+    pub fn go(&mut self, game: &Game) -> std::io::Result<()> {
+        if game.state != State::Playing {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "game is not being played"));
+        }
+        self.send("isready")?;
+        self.unready += 1;
+        self.send(&format!("position {}", game.to_notation()))?;
+        self.send("go")?;
+        self.asked = Some(Instant::now());
+        Ok(())
+    }
+
+    /// The answer to `go` without waiting: None while the bot is still thinking, then
+    /// Some with its move, or Some(None) if it failed to give a legal one in time
+    pub fn poll_move(&mut self, game: &Game) -> Option<Option<Move>> {
+        let asked = self.asked?;
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) if line.trim() == "readyok" => self.unready = self.unready.saturating_sub(1),
+                Ok(line) if self.unready == 0 && line.trim().starts_with("bestmove") => {
+                    self.asked = None;
+                    return Some(legal_move(game, line.trim()));
+                },
+                Ok(_) => (),
+                Err(TryRecvError::Empty) if asked.elapsed() < BOT_TIMEOUT => return None,
+                Err(_) => {
+                    self.asked = None;
+                    return Some(None);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, line: &str) -> std::io::Result<()> {
+        self.stdin.write_all((line.to_string() + "\n").as_bytes())?;
+        self.stdin.flush()
+    }
+
+    /// Skip lines until one matches, giving up after `BOT_TIMEOUT`
+    fn wait_for(&self, wanted: impl Fn(&str) -> bool) -> Option<String> {
+        loop {
+            let line = self.lines.recv_timeout(BOT_TIMEOUT).ok()?;
+            if wanted(line.trim()) {
+                return Some(line.trim().to_string());
+            }
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The move in a `bestmove` line, if it is one that can be made in `game`
+fn legal_move(game: &Game, line: &str) -> Option<Move> {
+    let m = parse_bestmove(line)?;
+    match game.get_cell(m.col, m.row) {
+        Ok(Cell::Empty) => Some(m),
+        _ => None
+    }
+}
+
+fn parse_bestmove(line: &str) -> Option<Move> {
+    match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        ["bestmove", cell, row, col] => Some(Move {
+            cell: parse_cell(cell)?,
+            row: row.parse().ok()?,
+            col: col.parse().ok()?
+        }),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::Mode;
+
+    #[test]
+    fn positions_are_sent_in_position_notation() {
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 2, 1);
+        assert_eq!(g.to_notation(), "S--/---/-O- L 0 0 S");
+    }
+
+    #[test]
+    fn parses_bestmove() {
+        assert_eq!(parse_bestmove("bestmove O 3 4"), Some(Move { cell: Cell::O, row: 3, col: 4 }));
+        assert_eq!(parse_bestmove("bestmove X 3 4"), None);
+        assert_eq!(parse_bestmove("bestmove"), None);
+    }
+
+    #[test]
+    fn launch_fails_for_missing_program() {
+        assert!(ExternalBot::launch("this_bot_does_not_exist").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn talks_to_bot_process() {
+        let script = "while read cmd rest; do case $cmd in \
+                      sos) echo 'id name test'; echo sosok;; \
+                      isready) echo readyok;; \
+                      go) echo 'bestmove O 1 2';; \
+                      quit) exit;; esac; done";
+        // Commands are split on whitespace, so the script goes in a file
+        let path = std::env::temp_dir().join(format!("sos_bot_{}.sh", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        let mut bot = ExternalBot::launch(&format!("sh {}", path.display())).unwrap();
+
+        let mut g = Game::new(Mode::Classic, 4);
+        g.state = State::Playing;
+        assert_eq!(bot.best_move(&g), Some(Move { cell: Cell::O, row: 1, col: 2 }));
+
+        // The same answer, collected without blocking
+        assert_eq!(bot.poll_move(&g), None);
+        bot.go(&g).unwrap();
+        let answer = loop {
+            if let Some(answer) = bot.poll_move(&g) {
+                break answer;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(answer, Some(Move { cell: Cell::O, row: 1, col: 2 }));
+        drop(bot);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn late_answers_are_not_taken_for_the_next_position() {
+        // Each answer names how many positions the bot was asked about before
+        let script = "n=0; while read cmd rest; do case $cmd in \
+                      sos) echo sosok;; \
+                      isready) echo readyok;; \
+                      go) sleep 1; echo \"bestmove S $n 0\"; n=$((n+1));; \
+                      quit) exit;; esac; done";
+        let path = std::env::temp_dir().join(format!("sos_slow_bot_{}.sh", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        let mut bot = ExternalBot::launch(&format!("sh {}", path.display())).unwrap();
+
+        let mut g = Game::new(Mode::Classic, 4);
+        g.state = State::Playing;
+        // Asked again before the first answer arrives, e.g. after an undo
+        bot.go(&g).unwrap();
+        bot.go(&g).unwrap();
+        let answer = loop {
+            if let Some(answer) = bot.poll_move(&g) {
+                break answer;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(answer, Some(Move { cell: Cell::S, row: 1, col: 0 }));
+        assert_eq!(bot.best_move(&g), Some(Move { cell: Cell::S, row: 2, col: 0 }));
+        drop(bot);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//!
+//! Chess clocks for timed games
+//!
+
+use std::time::{Duration, Instant};
+use crate::game::Game;
+use crate::game_enums::{State, TimeControl, TimeoutAction, Turn};
+
+/// Both players' clocks for one game
+///
+/// Times are measured against the `now` passed in, so the clock only moves while it is updated.
+pub struct Clock {
+    pub control: TimeControl,
+    pub on_timeout: TimeoutAction,
+    /// Time left on each player's total, for `TimeControl::Total`
+    left: Duration,
+    right: Duration,
+    /// Player whose time is running and when their turn began
+    turn: Turn,
+    turn_started: Instant,
+    /// Moves in the recording when the current turn began
+    moves: usize
+}
+
+impl Clock {
+    /// Start the clock for `game`, noting the time control in its recording
+    pub fn start(control: TimeControl, on_timeout: TimeoutAction, game: &mut Game, now: Instant) -> Self {
+        game.recording.time_control = control;
+        let total = match control {
+            TimeControl::Total(total, _) => total,
+            _ => Duration::ZERO
+        };
+        Self {
+            control,
+            on_timeout,
+            left: total,
+            right: total,
+            turn: game.turn,
+            turn_started: now,
+            moves: game.recording.moves.len()
+        }
+    }
+
+    /// Time `side` has left at `now`, or None in an untimed game
+    pub fn remaining(&self, side: Turn, now: Instant) -> Option<Duration> {
+        let running = if side == self.turn { now.saturating_duration_since(self.turn_started) } else { Duration::ZERO };
+        match self.control {
+            TimeControl::Untimed => None,
+            TimeControl::PerMove(limit) => Some(limit.saturating_sub(running)),
+            TimeControl::Total(..) => Some(match side {
+                Turn::Left => self.left,
+                Turn::Right => self.right
+            }.saturating_sub(running))
+        }
+    }
+
+    /// Act on a timeout, then charge the time of any moves made since the last update
+    ///
+    /// Call this regularly while the game is being played
+    pub fn update(&mut self, game: &mut Game, now: Instant) {
+        if game.state != State::Playing || self.control == TimeControl::Untimed {
+            return;
+        }
+        if game.recording.moves.len() == self.moves && self.remaining(self.turn, now) == Some(Duration::ZERO) {
+            match self.on_timeout {
+                TimeoutAction::RandomMove => game.make_random_move(),
+                TimeoutAction::ForfeitTurn => game.pass_turn(),
+                TimeoutAction::Lose => game.end(State::TimeOut(self.turn))
+            }
+        }
+
+        let moves = game.recording.moves.len();
+        if moves > self.moves {
+            let spent = now.saturating_duration_since(self.turn_started);
+            if let TimeControl::Total(_, increment) = self.control {
+                let total = match self.turn {
+                    Turn::Left => &mut self.left,
+                    Turn::Right => &mut self.right
+                };
+                *total = total.saturating_sub(spent) + increment;
+            }
+            // Several moves in one update all count as the first one's time
+            game.recording.move_times.push(spent);
+            game.recording.move_times.resize(moves, Duration::ZERO);
+            self.moves = moves;
+            self.turn = game.turn;
+            self.turn_started = now;
+        }
+    }
+}
+
+/// Time formatted as minutes and seconds, e.g. `4:05`
+pub fn time_string(time: Duration) -> String {
+    let seconds = time.as_secs_f64().ceil() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Cell, Mode};
+
+    fn timed(control: TimeControl, on_timeout: TimeoutAction, now: Instant) -> (Game, Clock) {
+        let mut g = Game::new(Mode::Classic, 4);
+        g.state = State::Playing;
+        let clock = Clock::start(control, on_timeout, &mut g, now);
+        (g, clock)
+    }
+
+    #[test]
+    fn total_time_is_charged_with_increment() {
+        let start = Instant::now();
+        let control = TimeControl::Total(Duration::from_secs(60), Duration::from_secs(2));
+        let (mut g, mut clock) = timed(control, TimeoutAction::Lose, start);
+
+        g.make_move(Cell::S, 0, 0);
+        clock.update(&mut g, start + Duration::from_secs(10));
+        assert_eq!(clock.remaining(Turn::Left, start + Duration::from_secs(10)), Some(Duration::from_secs(52)));
+        assert_eq!(clock.remaining(Turn::Right, start + Duration::from_secs(15)), Some(Duration::from_secs(55)));
+        assert_eq!(g.recording.move_times, vec![Duration::from_secs(10)]);
+        assert_eq!(g.recording.time_control, control);
+    }
+
+    #[test]
+    fn running_out_loses_the_game() {
+        let start = Instant::now();
+        let (mut g, mut clock) = timed(TimeControl::PerMove(Duration::from_secs(5)), TimeoutAction::Lose, start);
+        clock.update(&mut g, start + Duration::from_secs(4));
+        assert_eq!(g.state, State::Playing);
+        clock.update(&mut g, start + Duration::from_secs(5));
+        assert_eq!(g.state, State::TimeOut(Turn::Left));
+        assert_eq!(g.state.winner(), Some(Turn::Right));
+        assert_eq!(g.recording.ending, Some(g.state));
+    }
+
+    #[test]
+    fn forfeited_turn_passes_to_the_opponent() {
+        let start = Instant::now();
+        let (mut g, mut clock) = timed(TimeControl::PerMove(Duration::from_secs(5)), TimeoutAction::ForfeitTurn, start);
+        clock.update(&mut g, start + Duration::from_secs(6));
+        assert_eq!(g.turn, Turn::Right);
+        assert_eq!(g.recording.moves, vec![crate::recording::Move { cell: Cell::Empty, row: 0, col: 0 }]);
+        // The opponent's move gets a fresh limit
+        assert_eq!(clock.remaining(Turn::Right, start + Duration::from_secs(8)), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn random_move_is_played_on_timeout() {
+        let start = Instant::now();
+        let (mut g, mut clock) = timed(TimeControl::PerMove(Duration::from_secs(5)), TimeoutAction::RandomMove, start);
+        clock.update(&mut g, start + Duration::from_secs(5));
+        assert_eq!(g.recording.moves.len(), 1);
+        assert_ne!(g.recording.moves[0].cell, Cell::Empty);
+    }
+
+    #[test]
+    fn formats_minutes_and_seconds() {
+        assert_eq!(time_string(Duration::from_millis(245_300)), "4:06");
+        assert_eq!(time_string(Duration::ZERO), "0:00");
+    }
+}
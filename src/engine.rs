@@ -0,0 +1,321 @@
+//!
+//! Search engine used to suggest moves
+//!
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::game::Game;
+use crate::game_enums::{Cell, Mode, State, Turn};
+use crate::recording::Move;
+use crate::transposition::{Bound, Entry, TranspositionTable};
+
+/// Value of a won Simple game, larger than any possible score difference
+pub(crate) const WIN: i32 = 1000;
+
+/// Entries in the transposition table of each search
+pub(crate) const TABLE_SIZE: usize = 1 << 16;
+
+/// Why a suggested move was chosen
+#[derive(Clone, Debug, PartialEq)]
+pub enum HintReason {
+    /// Completes this many SOSs
+    Scores(u32),
+    /// Fills, without scoring or giving anything away, a cell where the opponent could otherwise
+    /// complete an SOS next turn
+    Blocks,
+    /// Does not give the opponent an SOS
+    Safe,
+    /// Every move gives something away; this one loses the least
+    LeastBad
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hint {
+    pub suggestion: Move,
+    pub reason: HintReason
+}
+
+/// Depth-limited negamax search over `Game` states
+#[derive(Clone)]
+pub struct Engine {
+    /// Number of letters placed ahead, counting extra turns
+    pub depth: u32,
+    /// Set to abandon a search, after which values are meaningless
+    stop: Option<Arc<AtomicBool>>
+}
+
+impl Engine {
+    pub fn new(depth: u32) -> Self {
+        Self { depth, stop: None }
+    }
+
+    /// The same engine, giving up as soon as `stop` is set
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Best move for the player on turn and its value from their point of view
+    ///
+    /// Returns None if the game is not being played
+    pub fn search(&self, game: &Game) -> Option<(Move, i32)> {
+        if game.state != State::Playing {
+            return None;
+        }
+
+        let mut table = TranspositionTable::new(TABLE_SIZE);
+        let mut best: Option<(Move, i32)> = None;
+        let mut alpha = -2 * WIN;
+        for m in root_moves(game) {
+            let value = self.root_value(game, &m, alpha, &mut table);
+            if best.is_none() || value > alpha {
+                alpha = value;
+                best = Some((m, value));
+            }
+        }
+        best
+    }
+
+    /// Value of making `m` from the point of view of the player on turn
+    pub fn move_value(&self, game: &Game, m: &Move) -> i32 {
+        self.root_value(game, m, -2 * WIN, &mut TranspositionTable::new(TABLE_SIZE))
+    }
+
+    /// Value of making `m` at the root of a search, exact if it is above `alpha`
+    pub(crate) fn root_value(&self, game: &Game, m: &Move, alpha: i32, table: &mut TranspositionTable) -> i32 {
+        self.child_value(&mut game.clone(), m, self.depth.max(1), alpha, 2 * WIN, table)
+    }
+
+    /// Whether `side` should take a draw, because it expects to do no better from here
+    pub fn accepts_draw(&self, game: &Game, side: Turn) -> bool {
+        match self.search(game) {
+            Some((_, value)) if game.turn == side => value <= 0,
+            Some((_, value)) => value >= 0,
+            None => false
+        }
+    }
+
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        self.search(game).map(|(m, _)| m)
+    }
+
+    /// Suggest a move for the player on turn along with the reason for it
+    pub fn hint(&self, game: &Game) -> Option<Hint> {
+        let suggestion = self.best_move(game)?;
+        let reason = hint_reason(game, &suggestion);
+        Some(Hint { suggestion, reason })
+    }
+
+    fn negamax(&self, game: &mut Game, depth: u32, mut alpha: i32, mut beta: i32, table: &mut TranspositionTable) -> i32 {
+        if depth == 0 || game.state != State::Playing {
+            return evaluate(game);
+        }
+        if self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return 0;
+        }
+
+        // Only results searched to the same depth are reused, so the answer never depends on move order
+        let hash = game.hash();
+        let mut stored_best = None;
+        if let Some(entry) = table.get(hash) {
+            if entry.depth == depth {
+                match entry.bound {
+                    Bound::Exact => return entry.value,
+                    Bound::Lower => alpha = alpha.max(entry.value),
+                    Bound::Upper => beta = beta.min(entry.value)
+                }
+                if alpha >= beta {
+                    return entry.value;
+                }
+            }
+            stored_best = entry.best.clone();
+        }
+        let original_alpha = alpha;
+
+        // Trying scoring moves first lets alpha-beta cut off more of the tree,
+        // and the best move from an earlier visit goes before all of them
+        let mut moves = game.legal_moves();
+        moves.sort_by_key(|m| (Some(m) != stored_best.as_ref(), std::cmp::Reverse(game.sos_if_placed(m.cell, m.row, m.col))));
+
+        let mut best = -2 * WIN;
+        let mut best_move = None;
+        for m in moves {
+            let value = self.child_value(game, &m, depth, alpha, beta, table);
+            if value > best {
+                best = value;
+                best_move = Some(m);
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        table.store(Entry { hash, depth, value: best, bound, best: best_move });
+        best
+    }
+
+    /// Value of making `m` from the point of view of the player making it
+    fn child_value(&self, game: &mut Game, m: &Move, depth: u32, alpha: i32, beta: i32, table: &mut TranspositionTable) -> i32 {
+        let turn = game.turn;
+        let played = game.play(m);
+        // Completing an SOS keeps the turn, so the value is not negated
+        let value = if game.turn == turn {
+            self.negamax(game, depth - 1, alpha, beta, table)
+        } else {
+            -self.negamax(game, depth - 1, -beta, -alpha, table)
+        };
+        game.unplay(m, played);
+        value
+    }
+}
+
+/// Moves in the order a search tries them
+///
+/// Ties go to the earliest move, so the moves we would rather explain come first
+pub(crate) fn root_moves(game: &Game) -> Vec<Move> {
+    let mut moves = game.legal_moves();
+    moves.sort_by_key(|m| {
+        let made = game.sos_if_placed(m.cell, m.row, m.col);
+        let safe = !game.gives_away_sos(m.cell, m.row, m.col);
+        (std::cmp::Reverse(made), !(safe && blocks_sos(game, m)), !safe)
+    });
+    moves
+}
+
+/// Static evaluation from the point of view of the player on turn
+pub fn evaluate(game: &Game) -> i32 {
+    let diff = game.left_score as i32 - game.right_score as i32;
+    let value = match game.get_mode() {
+        Mode::Classic => diff,
+        Mode::Simple => match game.state {
+            State::LeftWin => WIN,
+            State::RightWin => -WIN,
+            _ => 0
+        }
+    };
+    match game.turn {
+        Turn::Left => value,
+        Turn::Right => -value
+    }
+}
+
+/// Why `m` is worth playing, checked in the order the hint reasons are listed
+fn hint_reason(game: &Game, m: &Move) -> HintReason {
+    let made = game.sos_if_placed(m.cell, m.row, m.col);
+    if made > 0 {
+        HintReason::Scores(made)
+    } else if game.gives_away_sos(m.cell, m.row, m.col) {
+        HintReason::LeastBad
+    } else if blocks_sos(game, m) {
+        HintReason::Blocks
+    } else {
+        HintReason::Safe
+    }
+}
+
+/// Whether `m` takes a cell where the other letter completes an SOS, so the opponent cannot
+/// complete it there next turn
+fn blocks_sos(game: &Game, m: &Move) -> bool {
+    let other = match m.cell {
+        Cell::S => Cell::O,
+        Cell::O => Cell::S,
+        Cell::Empty => return false
+    };
+    game.sos_if_placed(m.cell, m.row, m.col) == 0 && game.sos_if_placed(other, m.row, m.col) > 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playing(mode: Mode, size: usize) -> Game {
+        let mut g = Game::new(mode, size);
+        g.state = State::Playing;
+        g
+    }
+
+    #[test]
+    fn search_returns_none_when_game_not_started() {
+        let g = Game::new(Mode::Classic, 5);
+        assert_eq!(Engine::new(2).search(&g), None);
+    }
+
+    #[test]
+    fn hint_completes_open_sos() {
+        let mut g = playing(Mode::Classic, 5);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        let hint = Engine::new(2).hint(&g).unwrap();
+
+        assert_eq!(hint.suggestion, Move { cell: Cell::O, row: 0, col: 1 });
+        assert_eq!(hint.reason, HintReason::Scores(1));
+    }
+
+    #[test]
+    fn hint_prefers_double_sos() {
+        let mut g = playing(Mode::Classic, 5);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 0, 1);
+        g.make_move(Cell::S, 2, 2);
+        g.make_move(Cell::O, 1, 2);
+        let hint = Engine::new(1).hint(&g).unwrap();
+
+        assert_eq!(hint.suggestion, Move { cell: Cell::S, row: 0, col: 2 });
+        assert_eq!(hint.reason, HintReason::Scores(2));
+    }
+
+    #[test]
+    fn hint_does_not_give_away_sos() {
+        let mut g = playing(Mode::Simple, 3);
+        g.make_move(Cell::S, 0, 0);
+        let hint = Engine::new(2).hint(&g).unwrap();
+        let m = hint.suggestion;
+
+        assert!(!g.gives_away_sos(m.cell, m.row, m.col));
+    }
+
+    #[test]
+    fn blocking_needs_an_sos_the_opponent_could_complete() {
+        let mut g = playing(Mode::Classic, 5);
+        g.make_move(Cell::S, 2, 2);
+        assert_eq!(hint_reason(&g, &Move { cell: Cell::S, row: 2, col: 3 }), HintReason::Safe);
+
+        let mut g = playing(Mode::Classic, 5);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        // O at b5 would complete S-O-S, and an S there stops the opponent doing it
+        assert_eq!(hint_reason(&g, &Move { cell: Cell::S, row: 0, col: 1 }), HintReason::Blocks);
+        assert_eq!(hint_reason(&g, &Move { cell: Cell::O, row: 0, col: 1 }), HintReason::Scores(1));
+        assert_eq!(hint_reason(&g, &Move { cell: Cell::S, row: 4, col: 4 }), HintReason::Safe);
+    }
+
+    #[test]
+    fn evaluate_is_from_point_of_view_of_player_on_turn() {
+        let mut g = playing(Mode::Classic, 5);
+        g.left_score = 3;
+        g.right_score = 1;
+        assert_eq!(evaluate(&g), 2);
+        g.turn = Turn::Right;
+        assert_eq!(evaluate(&g), -2);
+    }
+
+    #[test]
+    fn draw_declined_when_winning() {
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        assert!(Engine::new(2).accepts_draw(&g, Turn::Right));
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        // Player 1 can complete S-O-S at once
+        assert!(!Engine::new(2).accepts_draw(&g, Turn::Left));
+        assert!(Engine::new(2).accepts_draw(&g, Turn::Right));
+    }
+}
@@ -4,47 +4,212 @@
 
 use std::fmt::Error;
 use rand::Rng;
-use crate::game_enums::{Mode, Cell, Turn, State};
-use crate::recording::Recording;
+use crate::game_enums::{Mode, Cell, Turn, State, Threat, FirstTurn};
+use crate::bitboard::{Bitboard, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::notation::{mode_string, parse_board, parse_mode, parse_turn, rows_string, turn_string};
+use crate::recording::{Move, Recording, Setup};
+use crate::symmetry::Symmetry;
+use crate::zobrist;
 
 /// Contains game data such as board state, game mode, and player turn
 pub struct Game {
-    board: Vec<Vec<Cell>>,
+    board: Bitboard,
+    /// Zobrist hash of the letters on the board, kept up to date as they are placed
+    board_hash: u64,
     pub turn: Turn,
     // example trait usage: https://doc.rust-lang.org/book/ch17-03-oo-design-patterns.html
-    game_type: Option<Box<dyn WinCondition>>,
-    cells_filled: usize,
+    game_type: Option<Box<dyn WinCondition + Send + Sync>>,
     pub left_score: u32,
     pub right_score: u32,
     pub state: State,
-    pub recording: Recording
+    pub recording: Recording,
+    /// Moves taken back by `undo`, most recent last
+    redo_stack: Vec<Move>
+}
+
+/// Turn, scores and state from before a move made by `Game::play`, for `Game::unplay`
+pub(crate) struct Played {
+    applied: bool,
+    turn: Turn,
+    left_score: u32,
+    right_score: u32,
+    state: State
+}
+
+impl Clone for Game {
+    fn clone(&self) -> Self {
+        Self {
+            board: self.board,
+            board_hash: self.board_hash,
+            turn: self.turn,
+            game_type: win_condition(&self.recording.mode),
+            left_score: self.left_score,
+            right_score: self.right_score,
+            state: self.state,
+            recording: self.recording.clone(),
+            redo_stack: self.redo_stack.clone()
+        }
+    }
 }
 
 impl Game {
+    /// Panics if `board_size` is larger than `bitboard::MAX_BOARD_SIZE`
     pub fn new(mode: Mode, board_size: usize) -> Self {
         Self {
-            board: vec![vec![Cell::Empty; board_size]; board_size],
+            board: Bitboard::new(board_size),
+            board_hash: 0,
             turn: Turn::Left,
-            game_type: match mode {
-                Mode::Classic => Some(Box::new(ClassicGame {})),
-                Mode::Simple => Some(Box::new(SimpleGame {}))
-            },
-            cells_filled: 0,
+            game_type: win_condition(&mode),
             left_score: 0,
             right_score: 0,
             state: State::NotStarted,
-            recording: Recording::new(mode, board_size)
+            recording: Recording::new(mode, board_size),
+            redo_stack: Vec::new()
+        }
+    }
+
+    /// A game in progress from a position set up by hand, with `turn` to move
+    ///
+    /// Returns None if the board is a size the game does not support, or if the position is already decided, such as
+    /// a full board or a Simple game where someone has scored
+    pub fn from_position(mode: Mode, board: Bitboard, turn: Turn, left_score: u32, right_score: u32) -> Option<Self> {
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board.size()) {
+            return None;
+        }
+        let mut game = Game::new(mode, board.size());
+        game.board = board;
+        game.board_hash = zobrist::board_hash(&board);
+        game.left_score = left_score;
+        game.right_score = right_score;
+        game.set_first_turn(turn);
+        game.recording.setup = Some(Setup { board, left_score, right_score });
+        game.state = game.game_type.as_ref().unwrap().get_game_state(&game);
+        (game.state == State::Playing).then_some(game)
+    }
+
+    /// The game of a recording before its first move, from its setup if it has one, under the
+    /// same time control
+    pub fn at_start(recording: &Recording) -> Self {
+        let setup = recording.setup.as_ref().and_then(|setup| Game::from_position(recording.mode.clone(),
+            setup.board, recording.first_turn, setup.left_score, setup.right_score));
+        let mut game = setup.unwrap_or_else(|| {
+            let mut game = Game::new(recording.mode.clone(), recording.board_size);
+            game.set_first_turn(recording.first_turn);
+            game.state = State::Playing;
+            game
+        });
+        game.recording.time_control = recording.time_control;
+        game
+    }
+
+    /// Play out every move of a recording from the start
+    pub fn from_recording(recording: &Recording) -> Self {
+        let mut game = Game::at_start(recording);
+        for m in &recording.moves {
+            game.make_move(m.cell, m.row, m.col);
+        }
+        if let Some(ending) = recording.ending {
+            game.state = ending;
+        }
+        game
+    }
+
+    /// The position in text: board rows from the top, player on turn, both scores and mode,
+    /// e.g. `S-O/---/--S R 0 1 C`
+    ///
+    /// This is the position bots are sent; the moves that led to it are not included
+    pub fn to_notation(&self) -> String {
+        format!("{} {} {} {} {}", rows_string(self), turn_string(self.turn), self.left_score,
+                self.right_score, mode_string(&self.recording.mode))
+    }
+
+    /// A game in progress from a position written by `to_notation`
+    ///
+    /// Returns None if the text is not a position or the position is already decided
+    pub fn from_notation(s: &str) -> Option<Self> {
+        match s.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [rows, turn, left_score, right_score, mode] => Game::from_position(
+                parse_mode(mode)?,
+                parse_board(rows)?,
+                parse_turn(turn)?,
+                left_score.parse().ok()?,
+                right_score.parse().ok()?
+            ),
+            _ => None
+        }
+    }
+
+    /// End the game early, e.g. on time, keeping the result in the recording
+    pub fn end(&mut self, state: State) {
+        if self.state == State::Playing && state.is_over() {
+            self.state = state;
+            self.recording.ending = Some(state);
+        }
+    }
+
+    /// Choose who makes the first move, which is only possible before any move is made
+    pub fn set_first_turn(&mut self, turn: Turn) {
+        if self.recording.moves.is_empty() {
+            self.turn = turn;
+            self.recording.first_turn = turn;
         }
     }
 
     pub fn get_board_size(&self) -> usize {
-        return self.board.len();
+        self.board.size()
+    }
+
+    /// The board as letter masks, for fast searches
+    pub fn bitboard(&self) -> &Bitboard {
+        &self.board
+    }
+
+    /// Zobrist hash of the position: board, player on turn, score difference, mode and size
+    ///
+    /// Positions reached through different move orders hash the same
+    pub fn hash(&self) -> u64 {
+        let difference = self.left_score as i64 - self.right_score as i64;
+        self.board_hash ^ zobrist::state_key(self.turn, difference, &self.recording.mode, self.board.size())
+    }
+
+    /// The same game with the board turned or mirrored by `symmetry`, moves included
+    pub fn transformed(&self, symmetry: Symmetry) -> Game {
+        let size = self.board.size();
+        let mut game = self.clone();
+        game.board = self.board.transformed(symmetry);
+        game.board_hash = zobrist::board_hash(&game.board);
+        for m in game.recording.moves.iter_mut().chain(game.redo_stack.iter_mut()) {
+            *m = symmetry.apply_move(size, m);
+        }
+        if let Some(setup) = &mut game.recording.setup {
+            setup.board = setup.board.transformed(symmetry);
+        }
+        game
+    }
+
+    /// The image of this game shared by all of its rotations and reflections, and the symmetry
+    /// that turns this board into it
+    ///
+    /// Map a move found in the canonical game back with `symmetry.inverse()`
+    pub fn canonical(&self) -> (Game, Symmetry) {
+        let (_, symmetry) = self.board.canonical();
+        (self.transformed(symmetry), symmetry)
+    }
+
+    /// Like `hash`, but the same for every rotation and reflection of the position
+    pub fn canonical_hash(&self) -> u64 {
+        let difference = self.left_score as i64 - self.right_score as i64;
+        zobrist::board_hash(&self.board.canonical().0)
+            ^ zobrist::state_key(self.turn, difference, &self.recording.mode, self.board.size())
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.recording.mode.clone()
     }
 
     pub fn clear_grid(&mut self) {
-        let board_len = self.board.len();
         self.board.clear();
-        self.board.resize(board_len, vec![Cell::Empty; board_len]);
+        self.board_hash = 0;
     }
 
     /// Make a move on the game board
@@ -52,16 +217,48 @@ impl Game {
     /// # Example
     ///
     /// ```
-    /// use game::{Game, Mode, Cell};
+    /// use sos_game::game::Game;
+    /// use sos_game::game_enums::{Mode, Cell, State};
     ///
-    /// let mut g = Game::new(10, Mode::Classic);
-    /// g.make_move(4, 3, Cell::S);
+    /// let mut g = Game::new(Mode::Classic, 10);
+    /// g.state = State::Playing;
+    /// g.make_move(Cell::S, 4, 3);
     /// ```
     pub fn make_move(&mut self, input: Cell, row: usize, col: usize) {
-        if self.valid_cell(col, row) && self.board[row][col] == Cell::Empty && self.state == State::Playing {
-            self.board[row][col] = input;
-            self.cells_filled += 1;
-            let sos_made = self.sos_made(col, row);
+        // A new move starts a different line of play, so old redos no longer apply
+        if self.apply_move(input, row, col) {
+            self.redo_stack.clear();
+        }
+    }
+
+    /// End the game with `side` conceding it
+    pub fn resign(&mut self, side: Turn) {
+        self.end(State::Resigned(side));
+    }
+
+    /// End the game as a draw both players accepted
+    pub fn agree_draw(&mut self) {
+        self.end(State::DrawAgreed);
+    }
+
+    /// Give up the current turn without placing a letter, recorded as a move of `Cell::Empty`
+    pub fn pass_turn(&mut self) {
+        self.make_move(Cell::Empty, 0, 0);
+    }
+
+    fn apply_move(&mut self, input: Cell, row: usize, col: usize) -> bool {
+        if input == Cell::Empty {
+            if self.state != State::Playing {
+                return false;
+            }
+            self.recording.add_move(input, row, col);
+            self.switch_turn();
+            return true;
+        }
+        if self.valid_cell(col, row) && self.board.get(row, col) == Cell::Empty && self.state == State::Playing {
+            self.board.set(row, col, input);
+            self.board_hash ^= zobrist::cell_key(self.board.index(row, col), input);
+            let sos_made = self.board.sos_made(input, row, col);
             match self.turn {
                 Turn::Left => self.left_score += sos_made,
                 Turn::Right => self.right_score += sos_made
@@ -71,7 +268,80 @@ impl Game {
             if sos_made == 0 {
                 self.switch_turn();
             }
+            return true;
+        }
+        false
+    }
+
+    /// Make a move while searching, to be taken back with `unplay`
+    ///
+    /// Searches make and take back moves on one game rather than copying it, recording and all,
+    /// at every position they visit
+    pub(crate) fn play(&mut self, m: &Move) -> Played {
+        let (turn, left_score, right_score, state) = (self.turn, self.left_score, self.right_score, self.state);
+        let applied = self.apply_move(m.cell, m.row, m.col);
+        Played { applied, turn, left_score, right_score, state }
+    }
+
+    /// Take back the move `play` just made
+    pub(crate) fn unplay(&mut self, m: &Move, played: Played) {
+        if played.applied {
+            self.recording.moves.pop();
+            if m.cell != Cell::Empty {
+                self.board.set(m.row, m.col, Cell::Empty);
+                self.board_hash ^= zobrist::cell_key(self.board.index(m.row, m.col), m.cell);
+            }
+        }
+        self.turn = played.turn;
+        self.left_score = played.left_score;
+        self.right_score = played.right_score;
+        self.state = played.state;
+    }
+
+    /// Take back the last move, restoring board, scores, turn, state and recording
+    ///
+    /// Returns false if there is nothing to undo, or if the game was resigned, agreed drawn or
+    /// lost on time, which moves cannot take back
+    pub fn undo(&mut self) -> bool {
+        if self.recording.ending.is_some() {
+            return false;
         }
+        let mut moves = self.recording.moves.clone();
+        let last = match moves.pop() {
+            Some(m) => m,
+            None => return false
+        };
+
+        // Replaying from the start is simpler than reversing score and turn changes
+        let mut redo_stack = std::mem::take(&mut self.redo_stack);
+        redo_stack.push(last);
+        let mut move_times = std::mem::take(&mut self.recording.move_times);
+        move_times.truncate(moves.len());
+        *self = Game::at_start(&self.recording);
+        for m in moves {
+            self.apply_move(m.cell, m.row, m.col);
+        }
+        self.recording.move_times = move_times;
+        self.redo_stack = redo_stack;
+        true
+    }
+
+    /// Replay the most recently undone move
+    ///
+    /// Returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(m) => self.apply_move(m.cell, m.row, m.col),
+            None => false
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.recording.moves.is_empty() && self.recording.ending.is_none()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
     }
 
     pub fn make_random_move(&mut self) {
@@ -87,22 +357,86 @@ impl Game {
             _ => Cell::Empty
         };
 
-        let mut row = rng.gen_range(0..self.board.len());
-        let mut col = rng.gen_range(0..self.board.len());
-        while self.board[row][col] != Cell::Empty {
-            row = rng.gen_range(0..self.board.len());
-            col = rng.gen_range(0..self.board.len());
+        let size = self.board.size();
+        let mut row = rng.gen_range(0..size);
+        let mut col = rng.gen_range(0..size);
+        while self.board.get(row, col) != Cell::Empty {
+            row = rng.gen_range(0..size);
+            col = rng.gen_range(0..size);
         }
         self.make_move(input, row, col);
     }
 
-    fn valid_cell(&mut self, col: usize, row: usize) -> bool {
-        col < self.board.len() && row < self.board.len()
+    /// Every empty cell paired with both letters
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for row in 0..self.board.size() {
+            for col in 0..self.board.size() {
+                if self.board.get(row, col) == Cell::Empty {
+                    moves.push(Move { cell: Cell::S, row, col });
+                    moves.push(Move { cell: Cell::O, row, col });
+                }
+            }
+        }
+        moves
     }
 
-    pub fn get_cell(&mut self, x: usize, y: usize) -> Result<&Cell, Error> {
+    /// Number of SOSs that placing `input` at an empty cell would complete
+    pub fn sos_if_placed(&self, input: Cell, row: usize, col: usize) -> u32 {
+        if !self.valid_cell(col, row) || self.board.get(row, col) != Cell::Empty {
+            return 0;
+        }
+        self.board.sos_made(input, row, col)
+    }
+
+    /// Whether placing `input` at an empty cell would let the next player complete an SOS with it
+    ///
+    /// Only SOSs that use the new letter are considered, so open SOSs elsewhere on the board are ignored
+    pub fn gives_away_sos(&self, input: Cell, row: usize, col: usize) -> bool {
+        if !self.valid_cell(col, row) || self.board.get(row, col) != Cell::Empty {
+            return false;
+        }
+        let mut board = self.board;
+        board.set(row, col, input);
+
+        // Any SOS using the new letter has its empty cell within two steps of it
+        let last = board.size() - 1;
+        for y in row.saturating_sub(2)..=(row + 2).min(last) {
+            for x in col.saturating_sub(2)..=(col + 2).min(last) {
+                if board.get(y, x) == Cell::Empty
+                    && (board.sos_made(Cell::S, y, x) > self.board.sos_made(Cell::S, y, x)
+                        || board.sos_made(Cell::O, y, x) > self.board.sos_made(Cell::O, y, x)) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Classify placing `input` at a cell for the threat overlay
+    ///
+    /// Completing an SOS wins out over giving one away, since the extra turn comes first
+    pub fn threat(&self, input: Cell, row: usize, col: usize) -> Threat {
+        if self.sos_if_placed(input, row, col) > 0 {
+            Threat::Completes
+        } else if self.gives_away_sos(input, row, col) {
+            Threat::GivesAway
+        } else {
+            Threat::None
+        }
+    }
+
+    fn valid_cell(&self, col: usize, row: usize) -> bool {
+        col < self.board.size() && row < self.board.size()
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> Result<&Cell, Error> {
         match self.valid_cell(x, y) {
-            true => Ok(&self.board[y][x]),
+            true => Ok(match self.board.get(y, x) {
+                Cell::S => &Cell::S,
+                Cell::O => &Cell::O,
+                Cell::Empty => &Cell::Empty
+            }),
             false => Err(Error)
         }
     }
@@ -115,82 +449,18 @@ impl Game {
     }
 
     fn board_full(&self) -> bool {
-        self.cells_filled == self.board.len().pow(2)
-    }
-
-    fn sos_made(&mut self, x: usize, y: usize) -> u32 {
-        let mut count: u32 = 0;
-
-        match self.board[y][x] {
-            Cell::O => {
-                if y > 0 && y < self.board.len()-1 {
-                    if self.board[y-1][x] == Cell::S && self.board[y+1][x] == Cell::S {
-                        count += 1;
-                    }
-                    if x > 0 && x < self.board.len()-1 {
-                        if self.board[y-1][x+1] == Cell::S && self.board[y+1][x-1] == Cell::S {
-                            count += 1;
-                        }
-                        if self.board[y+1][x+1] == Cell::S && self.board[y-1][x-1] == Cell::S {
-                            count += 1;
-                        }
-                    }
-                }
-                if x > 0 && x < self.board.len()-1 {
-                    if self.board[y][x+1] == Cell::S && self.board[y][x-1] == Cell::S {
-                        count += 1;
-                    }
-                }
-            },
-            Cell::S => {
-                if y > 1 {
-                    if self.board[y-1][x] == Cell::O && self.board[y-2][x] == Cell::S {
-                        count += 1;
-                    }
-                    if x > 1 {
-                        if self.board[y-1][x-1] == Cell::O && self.board[y-2][x-2] == Cell::S {
-                            count += 1;
-                        }
-                    }
-                    if x < self.board.len()-2 {
-                        if self.board[y-1][x+1] == Cell::O && self.board[y-2][x+2] == Cell::S {
-                            count += 1;
-                        }
-                    }
-                }
-                if y < self.board.len()-2 {
-                    if self.board[y+1][x] == Cell::O && self.board[y+2][x] == Cell::S {
-                        count += 1;
-                    }
-                    if x > 1 {
-                        if self.board[y+1][x-1] == Cell::O && self.board[y+2][x-2] == Cell::S {
-                            count += 1;
-                        }
-                    }
-                    if x < self.board.len()-2 {
-                        if self.board[y+1][x+1] == Cell::O && self.board[y+2][x+2] == Cell::S {
-                            count += 1;
-                        }
-                    }
-                }
-                if x > 1 {
-                    if self.board[y][x-1] == Cell::O && self.board[y][x-2] == Cell::S {
-                        count += 1;
-                    }
-                }
-                if x < self.board.len()-2 {
-                    if self.board[y][x+1] == Cell::O && self.board[y][x+2] == Cell::S {
-                        count += 1;
-                    }
-                }
-            },
-            _ => ()
-        }
-        count
+        self.board.is_full()
     }
 }
 
-trait WinCondition {
+fn win_condition(mode: &Mode) -> Option<Box<dyn WinCondition + Send + Sync>> {
+    match mode {
+        Mode::Classic => Some(Box::new(ClassicGame {})),
+        Mode::Simple => Some(Box::new(SimpleGame {}))
+    }
+}
+
+trait WinCondition: Send + Sync {
     fn get_game_state(&self, game: &Game) -> State;
 }
 
@@ -228,14 +498,32 @@ impl WinCondition for SimpleGame {
     }
 }
 
+/// Who moves first under `choice`, given who moved first in the previous game if there was one
+pub fn choose_first_turn(choice: FirstTurn, previous: Option<Turn>) -> Turn {
+    match choice {
+        FirstTurn::Left => Turn::Left,
+        FirstTurn::Right => Turn::Right,
+        FirstTurn::Random => match rand::thread_rng().gen_bool(0.5) {
+            true => Turn::Left,
+            false => Turn::Right
+        },
+        FirstTurn::Alternate => match previous {
+            Some(Turn::Left) => Turn::Right,
+            _ => Turn::Left
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::time::Duration;
+    use crate::game_enums::TimeControl;
 
     #[test]
     fn game_starts_at_given_size() {
         let g = Game::new(Mode::Classic, 10);
-        assert!(g.board.len() == 10 && g.board[0].len() == 10);
+        assert!(g.board.rows().len() == 10 && g.board.rows()[0].len() == 10);
     }
 
     #[test]
@@ -264,7 +552,7 @@ mod test {
         let mut g = Game::new(Mode::Simple, 10);
         g.state = State::Playing; // must be in Playing state before make_move is called
         g.make_move(Cell::S, 6, 4);
-        assert_eq!(g.board[6][4], Cell::S);
+        assert_eq!(g.board.get(6, 4), Cell::S);
     }
 
     #[test]
@@ -274,7 +562,7 @@ mod test {
         g.make_random_move();
 
         let mut count = 0;
-        for line in g.board.clone() {
+        for line in g.board.rows() {
             for value in line {
                 if value != Cell::Empty {
                     count += 1;
@@ -289,7 +577,7 @@ mod test {
         let mut g = Game::new(Mode::Simple, 10);
         g.make_random_move();
 
-        assert_eq!(g.board, vec![vec![Cell::Empty; 10]; 10]);
+        assert_eq!(g.board.rows(), vec![vec![Cell::Empty; 10]; 10]);
     }
 
     #[test]
@@ -307,7 +595,7 @@ mod test {
         g.state = State::Playing;
         g.make_move(Cell::S, 6, 4);
         g.make_move(Cell::O, 6, 4);
-        assert_eq!(g.board[6][4], Cell::S);
+        assert_eq!(g.board.get(6, 4), Cell::S);
     }
 
     #[test]
@@ -325,7 +613,7 @@ mod test {
         let mut g = Game::new(Mode::Simple, 5);
         g.state = State::Playing;
         g.make_move(Cell::S, 6, 4);
-        assert_eq!(g.board, vec![vec![Cell::Empty; 5]; 5]);
+        assert_eq!(g.board.rows(), vec![vec![Cell::Empty; 5]; 5]);
     }
 
     #[test]
@@ -344,12 +632,12 @@ mod test {
         g.make_move(Cell::S, 6, 4);
         g.make_move(Cell::O, 5, 5);
         g.clear_grid();
-        assert_eq!(g.board, vec![vec![Cell::Empty; 10]; 10]);
+        assert_eq!(g.board.rows(), vec![vec![Cell::Empty; 10]; 10]);
     }
 
     #[test]
     fn get_cell_out_of_bounds_creates_error() {
-        let mut g = Game::new(Mode::Simple, 10);
+        let g = Game::new(Mode::Simple, 10);
         let result = g.get_cell(9, 10);
         assert_eq!(result, Err(Error));
     }
@@ -466,10 +754,361 @@ mod test {
         g.make_move(Cell::S, 2, 1);
 
         g.make_move(Cell::O, 1, 2);
+        g.make_move(Cell::O, 0, 2);
+
+        g.make_move(Cell::O, 2, 2);
+
+        assert_eq!(g.state, State::Draw);
+    }
+
+    #[test]
+    fn undo_restores_board_turn_and_recording() {
+        let mut g = Game::new(Mode::Simple, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 0, 1);
+        assert!(g.undo());
+
+        assert_eq!(g.board.get(0, 1), Cell::Empty);
+        assert_eq!(g.turn, Turn::Right);
+        assert_eq!(g.recording.moves.len(), 1);
+    }
+
+    #[test]
+    fn undo_keeps_the_time_control_and_remaining_move_times() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.recording.time_control = TimeControl::Total(Duration::from_secs(60), Duration::from_secs(2));
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 1, 1);
+        g.recording.move_times = vec![Duration::from_millis(1500), Duration::from_millis(2500)];
+        assert!(g.undo());
+
+        assert!(g.recording.as_string().starts_with("C,3,L,T60000+2000\n"));
+        assert_eq!(g.recording.move_times, vec![Duration::from_millis(1500)]);
+        assert_eq!(g.recording.moves.len(), 1);
+    }
+
+    #[test]
+    fn unplay_takes_back_what_play_did() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 0, 1);
+        let before = g.clone();
+
+        for m in [Move { cell: Cell::S, row: 0, col: 2 }, Move { cell: Cell::O, row: 2, col: 2 },
+                  Move { cell: Cell::Empty, row: 0, col: 0 }, Move { cell: Cell::S, row: 0, col: 0 }] {
+            let played = g.play(&m);
+            g.unplay(&m, played);
+            assert_eq!(g.board, before.board);
+            assert_eq!(g.hash(), before.hash());
+            assert_eq!((g.turn, g.left_score, g.right_score, g.state), (before.turn, 0, 0, State::Playing));
+            assert_eq!(g.recording, before.recording);
+        }
+    }
+
+    #[test]
+    fn games_ended_early_cannot_be_undone() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 1, 1);
+        g.resign(Turn::Left);
+        assert!(!g.can_undo());
+        assert!(!g.undo());
+
+        assert_eq!(g.recording.moves.len(), 2);
+        assert_eq!(g.state, State::Resigned(Turn::Left));
+    }
+
+    #[test]
+    fn undo_restores_score_and_state() {
+        let mut g = Game::new(Mode::Simple, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 0, 1);
         g.make_move(Cell::S, 0, 2);
+        assert_eq!(g.state, State::LeftWin);
+        assert!(g.undo());
 
+        assert_eq!(g.left_score, 0);
+        assert_eq!(g.turn, Turn::Left);
+        assert_eq!(g.state, State::Playing);
+    }
+
+    #[test]
+    fn undo_does_nothing_without_moves() {
+        let mut g = Game::new(Mode::Simple, 5);
+        g.state = State::Playing;
+        assert!(!g.undo());
+    }
+
+    #[test]
+    fn redo_replays_undone_move() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 2, 3);
+        g.undo();
+        assert!(g.redo());
+
+        assert_eq!(g.board.get(2, 3), Cell::S);
+        assert_eq!(g.turn, Turn::Right);
+        assert_eq!(g.recording.moves.len(), 1);
+    }
+
+    #[test]
+    fn new_move_clears_redo() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 2, 3);
+        g.undo();
+        g.make_move(Cell::O, 1, 1);
+        assert!(!g.can_redo());
+    }
+
+    #[test]
+    fn sos_if_placed_counts_without_moving() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        assert_eq!(g.sos_if_placed(Cell::O, 0, 1), 1);
+        assert_eq!(g.board.get(0, 1), Cell::Empty);
+    }
+
+    #[test]
+    fn gives_away_sos_when_o_placed_next_to_s() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
         g.make_move(Cell::S, 2, 2);
+        assert!(g.gives_away_sos(Cell::O, 2, 3));
+        assert!(!g.gives_away_sos(Cell::S, 2, 3));
+    }
 
-        assert_eq!(g.state, State::Draw);
+    #[test]
+    fn threat_marks_completing_cell() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 1, 1);
+        g.make_move(Cell::O, 2, 2);
+        assert_eq!(g.threat(Cell::S, 3, 3), Threat::Completes);
+        assert_eq!(g.threat(Cell::O, 3, 3), Threat::None);
+    }
+
+    #[test]
+    fn threat_marks_cell_that_gives_away_sos() {
+        let mut g = Game::new(Mode::Classic, 5);
+        g.state = State::Playing;
+        g.make_move(Cell::O, 2, 2);
+        assert_eq!(g.threat(Cell::S, 1, 1), Threat::GivesAway);
+        assert_eq!(g.threat(Cell::S, 2, 2), Threat::None);
+    }
+
+    #[test]
+    fn legal_moves_cover_empty_cells_with_both_letters() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 1, 1);
+        assert_eq!(g.legal_moves().len(), 16);
+    }
+
+    #[test]
+    fn first_turn_is_kept_through_undo_and_replay() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.set_first_turn(Turn::Right);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.set_first_turn(Turn::Left);
+        assert_eq!(g.turn, Turn::Left);
+        assert_eq!(g.recording.first_turn, Turn::Right);
+
+        assert!(g.undo());
+        assert_eq!(g.turn, Turn::Right);
+        g.make_move(Cell::S, 0, 0);
+        assert_eq!(Game::from_recording(&g.recording).turn, Turn::Left);
+    }
+
+    #[test]
+    fn set_up_position_plays_on_like_a_played_one() {
+        let mut played = Game::new(Mode::Classic, 3);
+        played.state = State::Playing;
+        played.make_move(Cell::S, 0, 0);
+        played.make_move(Cell::O, 0, 1);
+        played.make_move(Cell::S, 0, 2);
+
+        // The SOS keeps Player 1 on turn
+        let g = Game::from_position(Mode::Classic, *played.bitboard(), Turn::Left, 1, 0).unwrap();
+        assert_eq!(g.state, State::Playing);
+        assert_eq!(g.turn, Turn::Left);
+        assert_eq!(g.bitboard().filled(), 3);
+        assert_eq!(g.hash(), played.hash());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn decided_positions_cannot_be_set_up() {
+        let mut board = Bitboard::new(3);
+        assert!(Game::from_position(Mode::Simple, board, Turn::Left, 1, 0).is_none());
+        for row in 0..3 {
+            for col in 0..3 {
+                board.set(row, col, Cell::O);
+            }
+        }
+        assert!(Game::from_position(Mode::Classic, board, Turn::Left, 0, 0).is_none());
+    }
+
+    #[test]
+    fn notation_round_trips_a_position() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 0, 1);
+        g.make_move(Cell::S, 0, 2);
+        assert_eq!(g.to_notation(), "SOS/---/--- L 1 0 C");
+
+        let read = Game::from_notation(&g.to_notation()).unwrap();
+        assert_eq!(read.hash(), g.hash());
+        assert_eq!(read.to_notation(), g.to_notation());
+    }
+
+    #[test]
+    fn bad_notation_is_refused() {
+        for text in ["", "S--/---/--- L 0 0", "S--/--/--- L 0 0 C", "S--/-X-/--- L 0 0 C",
+                     "S--/---/--- X 0 0 C", "S--/---/--- L -1 0 C", "SOS/---/--- L 1 0 S"] {
+            assert!(Game::from_notation(text).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn boards_of_unsupported_sizes_are_refused() {
+        for size in [1, 2, MAX_BOARD_SIZE + 1] {
+            let rows = vec!["-".repeat(size); size].join("/");
+            assert!(Game::from_notation(&format!("{} L 0 0 C", rows)).is_none(), "{}", size);
+        }
+        assert!(Game::from_notation("---/---/--- L 0 0 C").is_some());
+    }
+
+    #[test]
+    fn undo_and_replay_start_from_the_setup() {
+        let mut board = Bitboard::new(3);
+        board.set(0, 0, Cell::S);
+        board.set(0, 2, Cell::S);
+        let mut g = Game::from_position(Mode::Classic, board, Turn::Right, 3, 0).unwrap();
+        g.make_move(Cell::O, 0, 1);
+        assert_eq!(g.right_score, 1);
+        assert_eq!(g.turn, Turn::Right);
+
+        let replayed = Game::from_recording(&g.recording);
+        assert_eq!((replayed.left_score, replayed.right_score), (3, 1));
+        assert_eq!(replayed.hash(), g.hash());
+
+        assert!(g.undo());
+        assert_eq!(*g.bitboard(), board);
+        assert_eq!((g.left_score, g.right_score, g.turn), (3, 0, Turn::Right));
+        assert!(!g.undo());
+    }
+
+    #[test]
+    fn alternate_first_turn_swaps_each_game() {
+        assert_eq!(choose_first_turn(FirstTurn::Alternate, None), Turn::Left);
+        assert_eq!(choose_first_turn(FirstTurn::Alternate, Some(Turn::Left)), Turn::Right);
+        assert_eq!(choose_first_turn(FirstTurn::Alternate, Some(Turn::Right)), Turn::Left);
+        assert_eq!(choose_first_turn(FirstTurn::Right, Some(Turn::Right)), Turn::Right);
+    }
+
+    #[test]
+    fn resigning_ends_the_game_for_the_opponent() {
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 1, 1);
+        g.resign(Turn::Right);
+        assert_eq!(g.state, State::Resigned(Turn::Right));
+        assert_eq!(g.state.winner(), Some(Turn::Left));
+        g.make_move(Cell::O, 0, 0);
+        assert_eq!(g.recording.moves.len(), 1);
+        assert_eq!(Game::from_recording(&g.recording).state, State::Resigned(Turn::Right));
+    }
+
+    #[test]
+    fn agreed_draw_only_ends_a_game_in_progress() {
+        let mut g = Game::new(Mode::Classic, 3);
+        g.agree_draw();
+        assert_eq!(g.state, State::NotStarted);
+        g.state = State::Playing;
+        g.agree_draw();
+        assert_eq!(g.state, State::DrawAgreed);
+        assert!(g.state.is_over());
+        assert_eq!(g.state.winner(), None);
+    }
+
+    #[test]
+    fn hash_ignores_move_order() {
+        let mut a = Game::new(Mode::Classic, 4);
+        a.state = State::Playing;
+        let mut b = a.clone();
+        let empty = a.hash();
+        for (cell, row, col) in [(Cell::S, 0, 0), (Cell::O, 2, 3), (Cell::S, 1, 1), (Cell::O, 3, 0)] {
+            a.make_move(cell, row, col);
+        }
+        for (cell, row, col) in [(Cell::S, 1, 1), (Cell::O, 3, 0), (Cell::S, 0, 0), (Cell::O, 2, 3)] {
+            b.make_move(cell, row, col);
+        }
+        assert_eq!(a.hash(), b.hash());
+        assert_ne!(a.hash(), empty);
+
+        a.undo();
+        assert_ne!(a.hash(), b.hash());
+        a.redo();
+        assert_eq!(a.hash(), b.hash());
+    }
+
+    #[test]
+    fn hash_covers_turn_score_and_mode() {
+        let mut g = Game::new(Mode::Classic, 4);
+        let start = g.hash();
+        g.turn = Turn::Right;
+        assert_ne!(g.hash(), start);
+        g.turn = Turn::Left;
+        g.left_score = 1;
+        assert_ne!(g.hash(), start);
+        g.left_score = 0;
+        assert_eq!(g.hash(), start);
+        assert_ne!(Game::new(Mode::Simple, 4).hash(), start);
+        assert_ne!(Game::new(Mode::Classic, 5).hash(), start);
+    }
+
+    #[test]
+    fn canonical_form_is_shared_by_symmetric_positions() {
+        let mut a = Game::new(Mode::Classic, 5);
+        a.state = State::Playing;
+        a.make_move(Cell::S, 0, 1);
+        a.make_move(Cell::O, 3, 2);
+        let b = a.transformed(Symmetry::Rotate90);
+        assert_eq!(b.get_cell(4, 1), Ok(&Cell::S));
+        assert_eq!(b.recording.moves[0], Move { cell: Cell::S, row: 1, col: 4 });
+        assert_ne!(a.hash(), b.hash());
+        assert_eq!(a.canonical_hash(), b.canonical_hash());
+
+        let (canonical, symmetry) = b.canonical();
+        assert_eq!(canonical.bitboard(), a.canonical().0.bitboard());
+        assert_eq!(canonical.hash(), a.canonical().0.hash());
+        // The canonical game undoes and replays like any other
+        let mut replayed = Game::from_recording(&canonical.recording);
+        assert_eq!(replayed.bitboard(), canonical.bitboard());
+        assert!(replayed.undo());
+        assert_eq!(canonical.transformed(symmetry.inverse()).bitboard(), b.bitboard());
+    }
+
+    #[test]
+    fn positions_in_recordings_dedupe_by_canonical_hash() {
+        let mut seen = std::collections::HashSet::new();
+        for (row, col) in [(0, 0), (0, 2), (2, 2), (1, 1), (0, 1)] {
+            let mut g = Game::new(Mode::Simple, 3);
+            g.state = State::Playing;
+            g.make_move(Cell::S, row, col);
+            seen.insert(g.canonical_hash());
+        }
+        // Corners are alike, and so are edges
+        assert_eq!(seen.len(), 3);
+    }
+}
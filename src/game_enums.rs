@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// Enumerates the possible SOS cell values
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub enum Cell { Empty, S, O}
@@ -7,8 +9,53 @@ pub enum Cell { Empty, S, O}
 pub enum Mode { Classic, Simple }
 
 /// Enumerates player turns
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Turn { Left, Right }
 
-#[derive(PartialEq, Debug)]
-pub enum State { LeftWin, RightWin, Draw, Playing, NotStarted }
\ No newline at end of file
+/// Enumerates game states
+///
+/// `TimeOut` and `Resigned` hold the player who lost that way; `DrawAgreed` ends the game by agreement
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State { LeftWin, RightWin, Draw, Playing, NotStarted, TimeOut(Turn), Resigned(Turn), DrawAgreed }
+
+impl State {
+    /// Whether the game has ended, however it ended
+    pub fn is_over(&self) -> bool {
+        !matches!(self, State::Playing | State::NotStarted)
+    }
+
+    /// Player who won a finished game, or None for a draw or an unfinished game
+    pub fn winner(&self) -> Option<Turn> {
+        match self {
+            State::LeftWin | State::TimeOut(Turn::Right) | State::Resigned(Turn::Right) => Some(Turn::Left),
+            State::RightWin | State::TimeOut(Turn::Left) | State::Resigned(Turn::Left) => Some(Turn::Right),
+            _ => None
+        }
+    }
+}
+
+/// Enumerates ways of choosing who moves first in a new game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FirstTurn { Left, Right, Random, Alternate }
+
+/// Enumerates the time limits a game can be played with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeControl {
+    Untimed,
+    /// The same time for every move
+    PerMove(Duration),
+    /// A total for the whole game, and time added after each move
+    Total(Duration, Duration)
+}
+
+/// Enumerates what happens when a player runs out of time
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeoutAction { RandomMove, ForfeitTurn, Lose }
+
+/// Enumerates what placing a letter on an empty cell would do
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Threat { None, Completes, GivesAway }
+
+/// Enumerates how a computer player picks its moves
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strategy { Random, Perfect, Engine, External }
@@ -0,0 +1,26 @@
+//!
+//! SOS game logic, engines and networking shared by the GUI and the server
+//!
+
+pub mod game;
+pub mod player;
+pub mod game_enums;
+pub mod notation;
+pub mod recording;
+pub mod engine;
+pub mod analysis;
+pub mod solver;
+pub mod network;
+pub mod server;
+pub mod bot;
+pub mod tournament;
+pub mod ratings;
+pub mod profiles;
+pub mod clock;
+pub mod bitboard;
+pub mod zobrist;
+pub mod transposition;
+pub mod symmetry;
+pub mod book;
+pub mod search;
+pub mod puzzle;
@@ -1,26 +1,67 @@
-mod game;
-mod player;
-mod game_enums;
-mod recording;
-
 use std::process;
+use std::sync::mpsc::{channel, Receiver};
 use std::thread;
-use std::time::Duration;
-use crate::game::Game;
-use crate::recording::Recording;
-use crate::game_enums::{Mode, Cell, Turn, State};
+use std::time::{Duration, Instant};
+use sos_game::game::{choose_first_turn, Game};
+use sos_game::recording::{Recording, Setup};
+use sos_game::bitboard::{Bitboard, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use sos_game::game_enums::{Mode, Cell, Turn, State, Threat, Strategy, FirstTurn, TimeControl, TimeoutAction};
 use eframe::egui;
-use eframe::egui::{FontFamily, FontId, TextStyle};
-use crate::player::Player;
+use eframe::egui::{Color32, FontFamily, FontId, RichText, TextStyle};
+use sos_game::player::{Player, Thinking, ENGINE_DEPTH};
+use sos_game::engine::{Engine, Hint, HintReason};
+use sos_game::analysis::Analysis;
+use sos_game::solver::{Solver, MAX_SOLVABLE_SIZE};
+use sos_game::network::{Event, Role, Session, DEFAULT_PORT};
+use sos_game::recording::Move;
+use sos_game::tournament::{Format, Tournament};
+use sos_game::ratings::Ratings;
+use sos_game::profiles::Profiles;
+use sos_game::clock::{time_string, Clock};
+use sos_game::book::{OpeningBook, BOOK_DEPTH};
+use sos_game::search::{default_threads, Evaluation, Search, SearchStatus, Searcher};
+use sos_game::puzzle::{Puzzle, Verdict, PUZZLE_DEPTH};
 
 const SIDE_PANEL_WIDTH: f32 = 80.0;
+const EVAL_BAR_WIDTH: f32 = 50.0;
 const BOARD_SIZE: f32 = 600.0;
-// WIDTH = board length + side panels + 45 pixels padding
-const WIDTH: f32 = BOARD_SIZE + 2.0 * SIDE_PANEL_WIDTH + 45.0;
+// WIDTH = board length + side panels + evaluation bar + 45 pixels padding
+const WIDTH: f32 = BOARD_SIZE + 2.0 * SIDE_PANEL_WIDTH + EVAL_BAR_WIDTH + 45.0;
 // HEIGHT = board length + top and bottom panels
 const HEIGHT: f32 = BOARD_SIZE + 75.0;
+/// How many letters ahead the hint engine looks
+const HINT_DEPTH: u32 = 2;
+/// How many letters ahead replays are analysed
+const ANALYSIS_DEPTH: u32 = 2;
+/// How many letters ahead the evaluation bar's engine looks
+const EVAL_DEPTH: u32 = 3;
+const PLAYER1_BAR_COLOR: Color32 = Color32::from_gray(220);
+const PLAYER2_BAR_COLOR: Color32 = Color32::from_gray(60);
+const HINT_COLOR: Color32 = Color32::from_rgb(90, 140, 60);
+const COMPLETES_COLOR: Color32 = Color32::from_rgb(60, 110, 60);
+const GIVES_AWAY_COLOR: Color32 = Color32::from_rgb(130, 50, 50);
+/// Where ratings are kept between runs
+const RATINGS_FILE: &str = "sos_ratings.txt";
+const PROFILES_FILE: &str = "sos_profiles.txt";
+/// Puzzles played instead of the built-in ones, if the file is there
+const PUZZLES_FILE: &str = "sos_puzzles.txt";
+/// Time controls offered when starting a game
+const TIME_CONTROLS: [(&str, TimeControl); 6] = [
+    ("None", TimeControl::Untimed),
+    ("10 s a move", TimeControl::PerMove(Duration::from_secs(10))),
+    ("30 s a move", TimeControl::PerMove(Duration::from_secs(30))),
+    ("1 min + 2 s", TimeControl::Total(Duration::from_secs(60), Duration::from_secs(2))),
+    ("3 min", TimeControl::Total(Duration::from_secs(180), Duration::ZERO)),
+    ("5 min + 5 s", TimeControl::Total(Duration::from_secs(300), Duration::from_secs(5)))
+];
 
 fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        run_command(&args[1..]);
+        return Ok(());
+    }
+
     // Configure eframe window
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(WIDTH, HEIGHT)),
@@ -34,14 +75,284 @@ fn main() -> Result<(), eframe::Error> {
     )
 }
 
+/// Run a command line subcommand instead of opening the window
+fn run_command(args: &[String]) {
+    match args[0].as_str() {
+        "analyze" => {
+            let (file, depth) = match args {
+                [_, file] => (file, ANALYSIS_DEPTH),
+                [_, file, depth] => (file, depth.parse().unwrap_or_else(|_| {
+                    eprintln!("Depth must be a number.");
+                    process::exit(1);
+                })),
+                _ => {
+                    eprintln!("Usage: sos_game analyze <recording.sos> [depth]");
+                    process::exit(1);
+                }
+            };
+            let recording = Recording::read_from_file(file.clone()).unwrap_or_else(|| {
+                eprintln!("Error opening file.");
+                process::exit(1);
+            });
+            print!("{}", Analysis::new(&recording, &Engine::new(depth)).as_string());
+        },
+        "solve" => {
+            let (mode, size) = match args {
+                [_, mode, size] => (mode, size.parse::<usize>().unwrap_or(0)),
+                _ => {
+                    eprintln!("Usage: sos_game solve <C|S> <size>");
+                    process::exit(1);
+                }
+            };
+            if !(MIN_BOARD_SIZE..=MAX_SOLVABLE_SIZE).contains(&size) {
+                eprintln!("Board size must be between {} and {}.", MIN_BOARD_SIZE, MAX_SOLVABLE_SIZE);
+                process::exit(1);
+            }
+            let mut game = Game::new(match mode.as_str() {
+                "S" => Mode::Simple,
+                _ => Mode::Classic
+            }, size);
+            game.state = State::Playing;
+
+            let mut solver = Solver::new();
+            if let (Some(value), Some(result)) = (solver.value(&game), solver.result(&game)) {
+                println!("Value for Player 1: {}\nResult: {:?}", value, result);
+            }
+        },
+        "tournament" => {
+            let usage = || {
+                eprintln!("Usage: sos_game tournament <round-robin|knockout> <C|S> <size> <player> <player>...\n\
+                           Players are random, perfect, engine or a bot command in quotes.\n\
+                           Perfect players play randomly on boards larger than {0}x{0}.", MAX_SOLVABLE_SIZE);
+                process::exit(1);
+            };
+            let [_, format, mode, size, players @ ..] = args else { usage() };
+            let format = match format.as_str() {
+                "round-robin" => Format::RoundRobin,
+                "knockout" => Format::Knockout,
+                _ => usage()
+            };
+            let mode = match mode.as_str() {
+                "S" => Mode::Simple,
+                _ => Mode::Classic
+            };
+            let size = size.parse::<usize>().unwrap_or(0);
+            if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) || players.len() < 2 {
+                usage();
+            }
+
+            let mut tournament = Tournament::new(format, mode, size);
+            for name in players {
+                let mut player = Player::new(Cell::S, true).with_name(name);
+                match name.as_str() {
+                    "random" => player.strategy = Strategy::Random,
+                    "perfect" => player.strategy = Strategy::Perfect,
+                    "engine" => player.strategy = Strategy::Engine,
+                    command => {
+                        player.strategy = Strategy::External;
+                        player.bot_command = command.to_string();
+                    }
+                }
+                tournament.add_entrant(name, player);
+            }
+            tournament.run();
+            for (i, game) in tournament.games.iter().enumerate() {
+                game.recording.write_to_file(format!("tournament_{}.sos", i + 1));
+            }
+            print!("{}", tournament.as_string());
+        },
+        "profiles" => {
+            let profiles = Profiles::read_from_file(PROFILES_FILE).unwrap_or_default();
+            for profile in &profiles.profiles {
+                println!("{}\n{}\n", profile.name, profile.as_string());
+            }
+        },
+        "record" => {
+            let [_, file, left, right] = args else {
+                eprintln!("Usage: sos_game record <recording.sos> <player 1> <player 2>");
+                process::exit(1);
+            };
+            let recording = Recording::read_from_file(file.clone()).unwrap_or_else(|| {
+                eprintln!("Error opening file.");
+                process::exit(1);
+            });
+            let mut profiles = Profiles::read_from_file(PROFILES_FILE).unwrap_or_default();
+            profiles.record_recording(left, right, &recording);
+            if let Err(e) = profiles.write_to_file(PROFILES_FILE) {
+                eprintln!("Could not save profiles: {}", e);
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("Unknown command {}. Available commands: analyze, solve, tournament, profiles, record", args[0]);
+            process::exit(1);
+        }
+    }
+}
+
+/// Combo box for a computer player's strategy
+fn strategy_select(ui: &mut egui::Ui, id: &str, player: &mut Player, board_size: usize, book: &OpeningBook) {
+    let strategy = &mut player.strategy;
+    egui::ComboBox::from_id_source(id)
+        .width(SIDE_PANEL_WIDTH - 10.0)
+        .selected_text(match strategy {
+            Strategy::Random => "Random",
+            Strategy::Perfect => "Perfect",
+            Strategy::Engine => "Engine",
+            Strategy::External => "External"
+        }).show_ui(ui, |ui| {
+        ui.selectable_value(strategy, Strategy::Random, "Random");
+        if board_size <= MAX_SOLVABLE_SIZE {
+            ui.selectable_value(strategy, Strategy::Perfect, "Perfect");
+        }
+        ui.selectable_value(strategy, Strategy::Engine, "Engine")
+            .on_hover_text(format!("Looks {} letters ahead", ENGINE_DEPTH));
+        ui.selectable_value(strategy, Strategy::External, "External");
+    });
+    // Perfect play is only offered on boards small enough to solve; the engine takes over above that
+    if board_size > MAX_SOLVABLE_SIZE && *strategy == Strategy::Perfect {
+        *strategy = Strategy::Engine;
+    }
+    if *strategy == Strategy::External {
+        ui.label("Bot command");
+        if ui.text_edit_singleline(&mut player.bot_command).changed() {
+            player.bot_error = None;
+        }
+        if let Some(error) = &player.bot_error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+    ui.checkbox(&mut player.use_book, "Opening book")
+        .on_hover_text(format!("{} positions from {} games", book.len(), book.games));
+}
+
+/// Combo box choosing a saved profile, and a field for typing a new name
+fn profile_select(ui: &mut egui::Ui, id: &str, player: &mut Player, profiles: &Profiles) {
+    egui::ComboBox::from_id_source(id)
+        .width(SIDE_PANEL_WIDTH - 10.0)
+        .selected_text(player.name.clone())
+        .show_ui(ui, |ui| {
+        for profile in &profiles.profiles {
+            ui.selectable_value(&mut player.name, profile.name.clone(), &profile.name);
+        }
+    });
+    ui.text_edit_singleline(&mut player.name);
+}
+
+/// Win, draw and loss counts for a side panel, with the full statistics on hover
+fn profile_stats(ui: &mut egui::Ui, player: &Player, profiles: &Profiles) {
+    if let Some(profile) = profiles.get(&player.name) {
+        ui.label(format!("{}/{}/{}", profile.wins, profile.draws, profile.losses))
+            .on_hover_text(profile.as_string());
+    }
+}
+
+/// Which engine the evaluation bar follows
+#[derive(Clone, Copy, PartialEq)]
+enum Evaluator { Off, Engine, Perfect }
+
+/// Position being set up by hand
+struct Editor {
+    setup: Setup,
+    turn: Turn,
+    /// Set when the position was already decided and could not be played from
+    decided: bool
+}
+
+/// Engine work done on another thread so the window keeps drawing, kept once it is done
+struct Background<T> {
+    receiver: Receiver<T>,
+    result: Option<T>
+}
+
+impl<T: Send + 'static> Background<T> {
+    fn start(work: impl FnOnce() -> T + Send + 'static) -> Self {
+        let (sender, receiver) = channel();
+        thread::spawn(move || { let _ = sender.send(work()); });
+        Self { receiver, result: None }
+    }
+
+    /// The result, or None while the work goes on
+    fn get(&mut self) -> Option<&T> {
+        if self.result.is_none() {
+            self.result = self.receiver.try_recv().ok();
+        }
+        self.result.as_ref()
+    }
+}
+
+/// A puzzle being attempted
+struct Attempt {
+    /// Index into the puzzles
+    index: usize,
+    /// Position last judged, or being judged
+    judged: u64,
+    verdict: Verdict,
+    /// The known solution's value, once the first judgement has worked it out
+    solution_value: Option<i32>,
+    /// Judgement under way, giving the solution's value and the verdict
+    judging: Option<Background<(i32, Verdict)>>
+}
+
 struct GameInterface {
     /// Decides board size next time player clicks Start or Reset
     next_board_size: usize,
     mode: Mode,
+    /// How the player to move first is picked when a game starts
+    first_turn: FirstTurn,
+    /// Who moved first in the last game started here
+    last_first_turn: Option<Turn>,
+    time_control: TimeControl,
+    on_timeout: TimeoutAction,
+    /// Running clocks of a timed game
+    clock: Option<Clock>,
+    /// Player offering a draw in a local game, and the number of moves when they offered
+    draw_offer: Option<(Turn, usize)>,
+    /// Number of moves when a computer last turned down a draw
+    draw_declined: Option<usize>,
+    /// Number of moves when a draw was offered to a computer, and whether it accepts
+    draw_asked: Option<(usize, Background<bool>)>,
     player1: Player,
     player2: Player,
     game: Game,
-    recording: Option<Recording>
+    recording: Option<Recording>,
+    /// Engine verdicts on the recording being replayed, worked out in the background
+    analysis: Option<Background<Analysis>>,
+    /// Rated games do not allow taking back moves
+    rated: bool,
+    ratings: Ratings,
+    profiles: Profiles,
+    /// Whether this game's result has already been counted, so undoing and finishing again does
+    /// not count it twice. Cleared only when another game begins
+    result_recorded: bool,
+    /// Suggested move, paired with the number of moves made when it was asked for
+    hint: Option<(usize, Background<Option<Hint>>)>,
+    /// Colour empty cells by what the current letter would do there
+    show_threats: bool,
+    /// Kept between moves so perfect players only solve each position once
+    solver: Solver,
+    /// Opening moves for computer players, read from a folder of recordings
+    book: OpeningBook,
+    /// Seconds a computer player or a replay waits before each move
+    move_delay: f32,
+    /// Computer's turn in progress: the position's hash, when the turn began and the move on its way
+    thinking: Option<(u64, Instant, Option<Thinking>)>,
+    evaluator: Evaluator,
+    /// Search behind the evaluation bar, with the position and evaluator it was started for
+    evaluation: Option<(u64, Evaluator, Search)>,
+    /// Latest evaluation, kept on the bar until the next one is ready
+    evaluation_shown: Option<Evaluation>,
+    /// Position editor, open before a game starts
+    editor: Option<Editor>,
+    /// Puzzles to work through, read when the first one is asked for
+    puzzles: Vec<Puzzle>,
+    /// Puzzle on the board
+    puzzle: Option<Attempt>,
+    /// Hosted or joined game on another machine
+    session: Option<Session>,
+    /// Address to host on or join
+    network_address: String,
+    network_status: String
 }
 
 impl Default for GameInterface {
@@ -49,16 +360,500 @@ impl Default for GameInterface {
         Self {
             next_board_size: 5,
             mode: Mode::Classic,
-            player1: Player::new(Cell::S, false),
-            player2: Player::new(Cell::S, false),
+            first_turn: FirstTurn::Left,
+            last_first_turn: None,
+            time_control: TimeControl::Untimed,
+            on_timeout: TimeoutAction::Lose,
+            clock: None,
+            draw_offer: None,
+            draw_declined: None,
+            draw_asked: None,
+            player1: Player::new(Cell::S, false).with_name("Player 1"),
+            player2: Player::new(Cell::S, false).with_name("Player 2"),
             game: Game::new(Mode::Classic, 5),
-            recording: None
+            recording: None,
+            analysis: None,
+            rated: false,
+            ratings: Ratings::read_from_file(RATINGS_FILE).unwrap_or_default(),
+            profiles: Profiles::read_from_file(PROFILES_FILE).unwrap_or_default(),
+            result_recorded: false,
+            hint: None,
+            show_threats: false,
+            solver: Solver::new(),
+            book: OpeningBook::default(),
+            move_delay: 1.0,
+            thinking: None,
+            evaluator: Evaluator::Off,
+            evaluation: None,
+            evaluation_shown: None,
+            editor: None,
+            puzzles: Vec::new(),
+            puzzle: None,
+            session: None,
+            network_address: format!("127.0.0.1:{}", DEFAULT_PORT),
+            network_status: String::new()
+        }
+    }
+}
+
+impl GameInterface {
+    /// Undo is only offered to humans playing a live, unrated game
+    fn undo_allowed(&self) -> bool {
+        !self.rated
+            && self.clock.is_none()
+            && self.recording.is_none()
+            && self.session.is_none()
+            && self.game.state != State::NotStarted
+            && !(self.player1.computer && self.player2.computer)
+    }
+
+    /// Hints are for the human on turn in a live game
+    fn hint_allowed(&self) -> bool {
+        self.recording.is_none() && self.game.state == State::Playing && !self.is_computer_turn()
+            && !self.is_remote_turn()
+    }
+
+    /// Moves can be made on the board, unless a puzzle attempt is over
+    fn board_open(&self) -> bool {
+        self.puzzle.as_ref().is_none_or(|attempt| attempt.verdict == Verdict::Going)
+    }
+
+    /// Set up puzzle `index` for the humans at this machine
+    fn start_puzzle(&mut self, index: usize) {
+        if self.puzzles.is_empty() {
+            self.puzzles = Puzzle::read_from_file(PUZZLES_FILE)
+                .filter(|puzzles| !puzzles.is_empty())
+                .unwrap_or_else(Puzzle::built_in);
+        }
+        let index = index % self.puzzles.len();
+        self.game = self.puzzles[index].game.clone();
+        self.puzzle = Some(Attempt {
+            index,
+            judged: self.game.hash(),
+            verdict: Verdict::Going,
+            solution_value: None,
+            judging: None
+        });
+        self.player1.computer = false;
+        self.player2.computer = false;
+        self.editor = None;
+        self.recording = None;
+        self.analysis = None;
+        self.clock = None;
+        self.thinking = None;
+        self.draw_offer = None;
+    }
+
+    /// Judge the puzzle attempt whenever the position changes
+    ///
+    /// Returns whether a judgement is still being worked out
+    fn update_puzzle(&mut self) -> bool {
+        if self.game.state == State::NotStarted {
+            self.puzzle = None;
+        }
+        let Some(attempt) = &mut self.puzzle else { return false };
+        let hash = self.game.hash();
+        if attempt.judged != hash {
+            attempt.judged = hash;
+            let puzzle = self.puzzles[attempt.index].clone();
+            let game = self.game.clone();
+            let solution_value = attempt.solution_value;
+            attempt.judging = Some(Background::start(move || {
+                let engine = Engine::new(PUZZLE_DEPTH);
+                let value = solution_value.unwrap_or_else(|| puzzle.solution_value(&engine));
+                (value, puzzle.judge(&game, value, &engine))
+            }));
+        }
+        if let Some(&(value, verdict)) = attempt.judging.as_mut().and_then(Background::get) {
+            attempt.solution_value = Some(value);
+            attempt.verdict = verdict;
+            attempt.judging = None;
+        }
+        attempt.judging.is_some()
+    }
+
+    /// Title, goal and result of the puzzle on the board, with buttons to retry or move on
+    fn puzzle_controls(&mut self, ui: &mut egui::Ui) {
+        let Some(Attempt { index, verdict, .. }) = self.puzzle else { return };
+        let puzzle = &self.puzzles[index];
+        ui.label(format!("Puzzle {}/{}: {}", index + 1, self.puzzles.len(), puzzle.title));
+        let size = puzzle.game.get_board_size();
+        ui.label(match verdict {
+            Verdict::Going => format!("{} to play and make the most of the turn", match puzzle.game.turn {
+                Turn::Left => "Player 1",
+                Turn::Right => "Player 2"
+            }),
+            Verdict::Solved => "Solved!".to_string(),
+            Verdict::Missed => format!("Missed. Best: {}", puzzle.solution.iter()
+                .map(|m| m.to_notation(size)).collect::<Vec<String>>().join(", "))
+        });
+        if ui.button("Retry").clicked() {
+            self.start_puzzle(index);
+        }
+        if ui.button("Next").clicked() {
+            self.start_puzzle(index + 1);
+        }
+    }
+
+    /// Whether the player on turn is on the other end of a network game, or we are only watching
+    fn is_remote_turn(&self) -> bool {
+        self.session.as_ref().is_some_and(|s| !s.is_local_turn(&self.game))
+    }
+
+    /// Networked games are between two humans, one on each machine
+    fn start_session(&mut self, session: std::io::Result<Session>) {
+        match session {
+            Ok(session) => {
+                self.network_status = match session.local_address() {
+                    Some(address) => format!("Waiting for a player on {}", address),
+                    None => "Waiting for the host".to_string()
+                };
+                self.player1.computer = false;
+                self.player2.computer = false;
+                self.editor = None;
+                self.result_recorded = false;
+                self.session = Some(session);
+            },
+            Err(e) => self.network_status = format!("Network error: {}", e)
+        }
+    }
+
+    fn close_session(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.close();
+        }
+        self.session = None;
+        self.network_status.clear();
+    }
+
+    /// A fresh game with the chosen size, mode and first player
+    fn new_game(&mut self) -> Game {
+        let mut game = Game::new(self.mode.clone(), self.next_board_size);
+        let turn = choose_first_turn(self.first_turn, self.last_first_turn);
+        game.set_first_turn(turn);
+        self.last_first_turn = Some(turn);
+        self.result_recorded = false;
+        game
+    }
+
+    /// Start a game from the editor's position, or an analysis with humans on both sides and the
+    /// evaluation bar showing
+    fn play_from_setup(&mut self, analyze: bool) {
+        let Some(editor) = &mut self.editor else { return };
+        let Some(mut game) = Game::from_position(self.mode.clone(), editor.setup.board, editor.turn,
+                                                 editor.setup.left_score, editor.setup.right_score) else {
+            editor.decided = true;
+            return;
+        };
+        self.editor = None;
+        self.thinking = None;
+        self.draw_offer = None;
+        self.draw_declined = None;
+        self.result_recorded = false;
+        if analyze {
+            self.player1.computer = false;
+            self.player2.computer = false;
+            if self.evaluator == Evaluator::Off {
+                self.evaluator = Evaluator::Engine;
+            }
+        }
+        self.clock = match self.time_control {
+            TimeControl::Untimed => None,
+            _ if analyze => None,
+            control => Some(Clock::start(control, self.on_timeout, &mut game, Instant::now()))
+        };
+        self.game = game;
+    }
+
+    /// Turn, scores and buttons of the position editor
+    fn editor_controls(&mut self, ui: &mut egui::Ui) {
+        let Some(editor) = &mut self.editor else { return };
+        ui.label("Turn:");
+        ui.radio_value(&mut editor.turn, Turn::Left, "Player 1");
+        ui.radio_value(&mut editor.turn, Turn::Right, "Player 2");
+        ui.label("Scores:");
+        ui.add(egui::DragValue::new(&mut editor.setup.left_score).clamp_range(0..=99));
+        ui.add(egui::DragValue::new(&mut editor.setup.right_score).clamp_range(0..=99));
+        if ui.button("Clear").clicked() {
+            editor.setup.board.clear();
+        }
+        if editor.decided {
+            ui.label("Position is already decided");
+        }
+        if ui.button("Play").clicked() {
+            self.play_from_setup(false);
+        }
+        if ui.button("Analyze").on_hover_text("Play both sides with the evaluation bar").clicked() {
+            self.play_from_setup(true);
+        }
+        if ui.button("Cancel").clicked() {
+            self.editor = None;
+        }
+    }
+
+    /// The position on the board in text, if it is one that can be played from
+    fn position_notation(&self) -> Option<String> {
+        match &self.editor {
+            Some(editor) => Game::from_position(self.mode.clone(), editor.setup.board, editor.turn,
+                                                editor.setup.left_score, editor.setup.right_score)
+                .map(|game| game.to_notation()),
+            None if self.game.state == State::NotStarted => None,
+            None => Some(self.game.to_notation())
+        }
+    }
+
+    /// Take in pasted text: a position opens in the editor, and a move is played for a human
+    fn paste(&mut self, text: &str) {
+        let text = text.trim();
+        if self.game.state != State::Playing && self.session.is_none() {
+            if let Some(game) = Game::from_notation(text) {
+                self.mode = game.get_mode();
+                self.next_board_size = game.get_board_size();
+                self.recording = None;
+                self.analysis = None;
+                self.clock = None;
+                self.thinking = None;
+                self.game.state = State::NotStarted;
+                self.editor = Some(Editor {
+                    setup: Setup { board: *game.bitboard(), left_score: game.left_score, right_score: game.right_score },
+                    turn: game.turn,
+                    decided: false
+                });
+            }
+        } else if self.hint_allowed() && self.board_open() {
+            if let Some(m) = Move::from_notation(text, self.game.get_board_size()).filter(|m| m.cell != Cell::Empty) {
+                match &mut self.session {
+                    Some(session) => session.submit_move(&mut self.game, m),
+                    None => self.game.make_move(m.cell, m.row, m.col)
+                }
+            }
+        }
+    }
+
+    /// Resign and draw offer buttons for the players at this machine
+    fn game_actions(&mut self, ui: &mut egui::Ui) {
+        if self.game.state != State::Playing || self.recording.is_some() || self.puzzle.is_some() {
+            return;
+        }
+        let moves = self.game.recording.moves.len();
+        if let Some(session) = &mut self.session {
+            if session.role == Role::Spectator {
+                return;
+            }
+            if ui.button("Resign").clicked() {
+                session.resign(&mut self.game);
+            }
+            match session.draw_offer(&self.game) {
+                Some(side) if side == session.local_side => { ui.label("Draw offered"); },
+                Some(_) => if ui.button("Accept Draw").clicked() {
+                    session.offer_draw(&mut self.game);
+                },
+                None => if ui.button("Offer Draw").clicked() {
+                    session.offer_draw(&mut self.game);
+                }
+            }
+            return;
+        }
+
+        // Offers lapse once another move is made
+        if self.draw_offer.is_some_and(|(_, offered_at)| offered_at != moves) {
+            self.draw_offer = None;
+        }
+        if let Some((side, _)) = self.draw_offer {
+            ui.label(format!("{} offers a draw", match side {
+                Turn::Left => "Player 1",
+                Turn::Right => "Player 2"
+            }));
+            if ui.button("Accept Draw").clicked() {
+                self.game.agree_draw();
+                self.draw_offer = None;
+            }
+            if ui.button("Decline").clicked() {
+                self.draw_offer = None;
+            }
+            return;
+        }
+        if self.is_computer_turn() {
+            return;
+        }
+        let side = self.game.turn;
+        if ui.button("Resign").clicked() {
+            self.game.resign(side);
+        }
+        if self.draw_declined == Some(moves) {
+            ui.label("Draw declined");
+        } else if self.draw_asked.is_some() {
+            ui.label("Draw offered");
+        } else if ui.button("Offer Draw").clicked() {
+            let opponent = match side {
+                Turn::Left => Turn::Right,
+                Turn::Right => Turn::Left
+            };
+            let opponent_is_computer = match opponent {
+                Turn::Left => self.player1.computer,
+                Turn::Right => self.player2.computer
+            };
+            if !opponent_is_computer {
+                self.draw_offer = Some((side, moves));
+            } else {
+                let game = self.game.clone();
+                self.draw_asked = Some((moves, Background::start(move || {
+                    Engine::new(HINT_DEPTH).accepts_draw(&game, opponent)
+                })));
+            }
+        }
+    }
+
+    /// Take up or turn down a draw offered to a computer, once it has thought the offer over
+    ///
+    /// Returns whether the computer is still thinking it over
+    fn update_draw_offer(&mut self) -> bool {
+        let Some((moves, accepts)) = &mut self.draw_asked else { return false };
+        // The offer lapses once another move is made
+        if *moves != self.game.recording.moves.len() || self.game.state != State::Playing {
+            self.draw_asked = None;
+            return false;
+        }
+        let Some(&accepts) = accepts.get() else { return true };
+        let moves = *moves;
+        self.draw_asked = None;
+        if accepts {
+            self.game.agree_draw();
+        } else {
+            self.draw_declined = Some(moves);
+        }
+        false
+    }
+
+    /// Time left for a side panel, empty in an untimed game
+    fn clock_label(&self, side: Turn) -> String {
+        match self.clock.as_ref().and_then(|clock| clock.remaining(side, Instant::now())) {
+            Some(time) => format!("Time: {}", time_string(time)),
+            None => String::new()
         }
     }
+
+    /// Update the profiles, and the ratings for a rated game, once when a game played here finishes
+    fn record_result(&mut self) {
+        match self.game.state {
+            State::Playing | State::NotStarted => (),
+            state => if !self.result_recorded {
+                self.result_recorded = true;
+                let watching = self.session.as_ref().is_some_and(|s| s.role == Role::Spectator);
+                if self.recording.is_some() || watching || self.puzzle.is_some() {
+                    return;
+                }
+                self.profiles.record(&self.player1.name, &self.player2.name, &self.game);
+                let _ = self.profiles.write_to_file(PROFILES_FILE);
+                if self.rated {
+                    self.ratings.record(&self.player1.name, &self.player2.name, &self.game.get_mode(), state);
+                    let _ = self.ratings.write_to_file(RATINGS_FILE);
+                }
+            }
+        }
+    }
+
+    /// Keep the evaluation bar's search in step with the position
+    ///
+    /// Returns whether a search is still running
+    fn update_evaluation(&mut self) -> bool {
+        let searcher = match self.evaluator {
+            Evaluator::Off => {
+                self.evaluation = None;
+                self.evaluation_shown = None;
+                return false;
+            },
+            Evaluator::Perfect if Solver::can_solve(&self.game) => Searcher::Solver(self.solver.clone()),
+            // Boards too large to solve get the engine instead
+            Evaluator::Engine | Evaluator::Perfect => Searcher::Engine(EVAL_DEPTH)
+        };
+        if self.game.state != State::Playing {
+            self.evaluation = None;
+            self.evaluation_shown = self.game.state.is_over().then(|| Evaluation::of_finished(&self.game));
+            return false;
+        }
+
+        let hash = self.game.hash();
+        if self.evaluation.as_ref().is_none_or(|(h, evaluator, _)| *h != hash || *evaluator != self.evaluator) {
+            self.evaluation = Some((hash, self.evaluator, Search::start(&self.game, searcher, default_threads())));
+        }
+        let (_, _, search) = self.evaluation.as_mut().unwrap();
+        match search.poll() {
+            SearchStatus::Running => true,
+            SearchStatus::Done(_) => {
+                self.evaluation_shown = search.evaluation();
+                false
+            }
+        }
+    }
+
+    /// Rating line for a side panel, in the mode being played or about to be
+    fn rating_label(&self, player: &Player) -> String {
+        let mode = match self.game.state {
+            State::NotStarted => &self.mode,
+            _ => &self.recording.as_ref().map_or(self.game.get_mode(), |r| r.mode.clone())
+        };
+        let rating = self.ratings.get(&player.name, mode);
+        format!("Rating: {:.0}", rating.rating)
+    }
+
+    fn is_computer_turn(&self) -> bool {
+        match self.game.turn {
+            Turn::Left => self.player1.computer,
+            Turn::Right => self.player2.computer
+        }
+    }
+
+    /// Take back moves until it is a human's turn again
+    fn undo(&mut self) {
+        if !self.undo_allowed() {
+            return;
+        }
+        while self.game.undo() && self.is_computer_turn() {}
+    }
+
+    /// Replay undone moves until it is a human's turn again
+    fn redo(&mut self) {
+        if !self.undo_allowed() {
+            return;
+        }
+        while self.game.redo() && self.is_computer_turn() && self.game.state == State::Playing {}
+    }
 }
 
 impl eframe::App for GameInterface {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(session) = &mut self.session {
+            for event in session.poll(&mut self.game) {
+                self.network_status = match event {
+                    Event::Started => match (session.role, session.local_side) {
+                        (Role::Spectator, _) => "Watching".to_string(),
+                        (_, Turn::Left) => "Connected as Player 1".to_string(),
+                        (_, Turn::Right) => "Connected as Player 2".to_string()
+                    },
+                    Event::Error(reason) => format!("Host: {}", reason),
+                    Event::DrawOffered => "Opponent offers a draw".to_string(),
+                    Event::Disconnected if session.role == Role::Spectator => "Host disconnected".to_string(),
+                    Event::Disconnected => "Opponent disconnected".to_string()
+                };
+            }
+            // Keep checking for network messages without waiting for mouse movement
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        // A hint only applies to the position it was asked for
+        if let Some((move_count, _)) = &self.hint {
+            if *move_count != self.game.recording.moves.len() || !self.hint_allowed() {
+                self.hint = None;
+            }
+        }
+        // Keep checking on engine work without waiting for mouse movement
+        let hint_pending = self.hint.as_mut().is_some_and(|(_, hint)| hint.get().is_none());
+        let analysis_pending = self.analysis.as_mut().is_some_and(|analysis| analysis.get().is_none());
+        if hint_pending || analysis_pending || self.update_draw_offer() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
         // Top panel contains board size and game mode select
         egui::TopBottomPanel::top("top")
             .resizable(false)
@@ -67,7 +862,7 @@ impl eframe::App for GameInterface {
             ui.horizontal_top(|ui| {
                 ui.vertical(|ui| {
                     ui.label("Board Size");
-                    ui.add(egui::Slider::new(&mut self.next_board_size, 3..=10));
+                    ui.add(egui::Slider::new(&mut self.next_board_size, MIN_BOARD_SIZE..=MAX_BOARD_SIZE));
                 });
                 ui.vertical(|ui| {
                     ui.label("Mode");
@@ -87,55 +882,206 @@ impl eframe::App for GameInterface {
                         });
                     }
                 });
+                ui.vertical(|ui| {
+                    ui.label("Clock");
+                    let control_name = |control: TimeControl| TIME_CONTROLS.iter()
+                        .find(|(_, c)| *c == control)
+                        .map_or("", |(name, _)| name);
+                    let timeout_name = |action: TimeoutAction| match action {
+                        TimeoutAction::RandomMove => "Random move",
+                        TimeoutAction::ForfeitTurn => "Skip turn",
+                        TimeoutAction::Lose => "Lose"
+                    };
+                    if self.game.state != State::Playing {
+                        egui::ComboBox::from_id_source("time_control")
+                            .selected_text(control_name(self.time_control))
+                            .show_ui(ui, |ui| {
+                            for (name, control) in TIME_CONTROLS {
+                                ui.selectable_value(&mut self.time_control, control, name);
+                            }
+                        });
+                        if self.time_control != TimeControl::Untimed {
+                            egui::ComboBox::from_id_source("on_timeout")
+                                .selected_text(timeout_name(self.on_timeout))
+                                .show_ui(ui, |ui| {
+                                for action in [TimeoutAction::RandomMove, TimeoutAction::ForfeitTurn, TimeoutAction::Lose] {
+                                    ui.selectable_value(&mut self.on_timeout, action, timeout_name(action));
+                                }
+                            });
+                        }
+                    } else {
+                        let _ = ui.button(control_name(self.game.recording.time_control));
+                    }
+                });
+                ui.vertical(|ui| {
+                    ui.label("First");
+                    let first_turn_name = |first_turn: FirstTurn| match first_turn {
+                        FirstTurn::Left => "Player 1",
+                        FirstTurn::Right => "Player 2",
+                        FirstTurn::Random => "Random",
+                        FirstTurn::Alternate => "Alternate"
+                    };
+                    if self.game.state != State::Playing {
+                        egui::ComboBox::from_id_source("first_turn")
+                            .selected_text(first_turn_name(self.first_turn))
+                            .show_ui(ui, |ui| {
+                            for first_turn in [FirstTurn::Left, FirstTurn::Right, FirstTurn::Random, FirstTurn::Alternate] {
+                                ui.selectable_value(&mut self.first_turn, first_turn, first_turn_name(first_turn));
+                            }
+                        });
+                    } else {
+                        let _ = ui.button(first_turn_name(self.first_turn));
+                    }
+                });
                 ui.vertical(|ui| {
                     ui.label("");
                     if self.game.state == State::NotStarted {
-                        if ui.button("Start").clicked() {
-                            self.game = Game::new(self.mode.clone(), self.next_board_size.clone());
+                        if self.session.is_none() && self.editor.is_none() && ui.button("Start").clicked() {
+                            self.game = self.new_game();
                             self.game.state = State::Playing;
+                            self.thinking = None;
+                            self.draw_offer = None;
+                            self.draw_declined = None;
+                            self.clock = match self.time_control {
+                                TimeControl::Untimed => None,
+                                control => Some(Clock::start(control, self.on_timeout, &mut self.game, Instant::now()))
+                            };
                         }
                     } else {
                         if ui.button("Reset").clicked() {
+                            self.close_session();
+                            // Dropping a search stops its workers
+                            self.thinking = None;
+                            self.clock = None;
                             self.game.clear_grid();
                             self.recording = None;
+                            self.analysis = None;
                             self.game.state = State::NotStarted;
                         }
                     }
                 });
                 ui.vertical(|ui| {
                     ui.label("");
-                    if self.game.state == State::NotStarted {
+                    if self.game.state == State::NotStarted && self.session.is_none() && self.editor.is_none() {
+                        if ui.button("Edit").on_hover_text("Set up a position to play or analyze from").clicked() {
+                            self.editor = Some(Editor {
+                                setup: Setup { board: Bitboard::new(self.next_board_size), left_score: 0, right_score: 0 },
+                                turn: Turn::Left,
+                                decided: false
+                            });
+                        }
+                        if ui.button("Puzzle").on_hover_text("Find the best chain of SOSs").clicked() {
+                            self.start_puzzle(0);
+                        }
                         if ui.button("Load").clicked() {
-                            let open_file: String;
-                            match tinyfiledialogs::open_file_dialog("Open", "", Some((&["*.sos"], ".sos"))) {
-                                Some(file) => open_file = file,
-                                None => open_file = "null".to_string(),
-                            }
+                            let open_file = match tinyfiledialogs::open_file_dialog("Open", "", Some((&["*.sos"], ".sos"))) {
+                                Some(file) => file,
+                                None => "null".to_string(),
+                            };
                             let recording = Recording::read_from_file(open_file).unwrap_or_else(|| {
                                 eprintln!("Error opening file.");
                                 process::exit(1);
                             });
                             self.next_board_size = recording.board_size;
                             self.mode = recording.mode.clone();
+                            let analyzed = recording.clone();
+                            self.analysis = Some(Background::start(move || {
+                                Analysis::new(&analyzed, &Engine::new(ANALYSIS_DEPTH))
+                            }));
                             self.recording = Some(recording);
+                            self.clock = None;
+                            self.thinking = None;
+                            self.result_recorded = false;
                             self.player1.computer = true;
                             self.player2.computer = true;
 
-                            self.game = Game::new(self.mode.clone(), self.next_board_size);
-                            self.game.state = State::Playing;
+                            self.game = Game::at_start(self.recording.as_ref().unwrap());
                         }
                     }
-                    else if self.game.state != State::Playing {
-                        if ui.button("Save").clicked() {
-                            let save_file: String;
-                            match tinyfiledialogs::save_file_dialog("Save", "recording.sos") {
-                                Some(file) => save_file = file,
-                                None => save_file = "null".to_string(),
+                    else if self.game.state != State::Playing && ui.button("Save").clicked() {
+                        let save_file = match tinyfiledialogs::save_file_dialog("Save", "recording.sos") {
+                            Some(file) => file,
+                            None => "null".to_string(),
+                        };
+                        self.game.recording.write_to_file(save_file);
+                    }
+                });
+                ui.vertical(|ui| {
+                    ui.label("");
+                    ui.checkbox(&mut self.show_threats, "Threats");
+                });
+                ui.vertical(|ui| {
+                    ui.label("Delay");
+                    ui.spacing_mut().slider_width = 60.0;
+                    ui.add(egui::Slider::new(&mut self.move_delay, 0.0..=3.0).suffix(" s"))
+                        .on_hover_text("Time the computer and replays wait before each move");
+                });
+                ui.vertical(|ui| {
+                    ui.label("Eval");
+                    let evaluator_name = |evaluator: Evaluator| match evaluator {
+                        Evaluator::Off => "Off",
+                        Evaluator::Engine => "Engine",
+                        Evaluator::Perfect => "Perfect"
+                    };
+                    egui::ComboBox::from_id_source("evaluator")
+                        .width(70.0)
+                        .selected_text(evaluator_name(self.evaluator))
+                        .show_ui(ui, |ui| {
+                        for evaluator in [Evaluator::Off, Evaluator::Engine, Evaluator::Perfect] {
+                            ui.selectable_value(&mut self.evaluator, evaluator, evaluator_name(evaluator));
+                        }
+                    });
+                });
+                ui.vertical(|ui| {
+                    ui.label("");
+                    if self.game.state != State::Playing && ui.button("Book")
+                        .on_hover_text("Build the opening book from a folder of recordings").clicked() {
+                        if let Some(folder) = tinyfiledialogs::select_folder_dialog("Opening book", "") {
+                            match OpeningBook::from_directory(&folder, BOOK_DEPTH) {
+                                Ok(book) => self.book = book,
+                                Err(e) => eprintln!("Could not read {}: {}", folder, e)
                             }
-                            self.game.recording.write_to_file(save_file);
                         }
                     }
                 });
+                ui.vertical(|ui| {
+                    ui.label("Network");
+                    if self.game.state == State::NotStarted {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.network_address).desired_width(110.0));
+                            if self.session.is_some() {
+                                if ui.button("Cancel").clicked() {
+                                    self.close_session();
+                                }
+                            } else {
+                                // The guest is only told the mode and size, so set-up positions stay at home
+                                if ui.add_enabled(self.editor.is_none(), egui::Button::new("Host"))
+                                    .on_hover_text("Network games start from an empty board and have no clock")
+                                    .on_disabled_hover_text("Set-up positions cannot be played over the network")
+                                    .clicked() {
+                                    // The guest plays whatever mode and size the host has chosen
+                                    self.game = self.new_game();
+                                    self.clock = None;
+                                    self.start_session(Session::host(&self.network_address));
+                                }
+                                if ui.button("Join").clicked() {
+                                    self.start_session(Session::join(&self.network_address));
+                                }
+                                if ui.button("Watch").clicked() {
+                                    self.start_session(Session::watch(&self.network_address));
+                                }
+                            }
+                        });
+                    }
+                });
+                ui.vertical(|ui| {
+                    ui.label("");
+                    if self.game.state != State::Playing {
+                        ui.checkbox(&mut self.rated, "Rated");
+                    } else if self.rated {
+                        ui.label("Rated");
+                    }
+                });
             });
         });
 
@@ -144,10 +1090,14 @@ impl eframe::App for GameInterface {
             .resizable(false)
             .exact_width(SIDE_PANEL_WIDTH)
             .show(ctx, |ui| {
-                ui.label("Player 1");
                 if self.game.state != State::Playing {
+                    profile_select(ui, "left_profile", &mut self.player1, &self.profiles);
                     ui.checkbox(&mut self.player1.computer, "Computer");
+                    if self.player1.computer {
+                        strategy_select(ui, "left_strategy", &mut self.player1, self.next_board_size, &self.book);
+                    }
                 } else {
+                    ui.label(&self.player1.name);
                     ui.label(
                         match self.player1.computer {
                             true => "Computer",
@@ -160,6 +1110,9 @@ impl eframe::App for GameInterface {
                     ui.radio_value(&mut self.player1.pmove, Cell::O, "O");
                 }
                 ui.label(format!("Score: {}", self.game.left_score));
+                ui.label(self.clock_label(Turn::Left));
+                ui.label(self.rating_label(&self.player1));
+                profile_stats(ui, &self.player1, &self.profiles);
         });
 
         // Right panel contains Player 2's controls
@@ -167,10 +1120,14 @@ impl eframe::App for GameInterface {
             .resizable(false)
             .exact_width(SIDE_PANEL_WIDTH + 20.0) // I have no idea why, but this has to be larger than the left panel
             .show(ctx, |ui| {
-                ui.label("Player 2");
                 if self.game.state != State::Playing {
+                    profile_select(ui, "right_profile", &mut self.player2, &self.profiles);
                     ui.checkbox(&mut self.player2.computer, "Computer");
+                    if self.player2.computer {
+                        strategy_select(ui, "right_strategy", &mut self.player2, self.next_board_size, &self.book);
+                    }
                 } else {
+                    ui.label(&self.player2.name);
                     ui.label(
                         match self.player2.computer {
                             true => "Computer",
@@ -183,12 +1140,35 @@ impl eframe::App for GameInterface {
                     ui.radio_value(&mut self.player2.pmove, Cell::O, "O");
                 }
                 ui.label(format!("Score: {}", self.game.right_score));
+                ui.label(self.clock_label(Turn::Right));
+                ui.label(self.rating_label(&self.player2));
+                profile_stats(ui, &self.player2, &self.profiles);
         });
 
+        // Evaluation bar between the board and Player 2's controls, Player 1's share filling from the bottom
+        if self.evaluator != Evaluator::Off {
+            egui::SidePanel::right("evaluation")
+                .resizable(false)
+                .exact_width(EVAL_BAR_WIDTH)
+                .show(ctx, |ui| {
+                    ui.label(self.evaluation_shown.map_or(String::new(), |e| e.as_string()));
+                    let (rect, response) = ui.allocate_exact_size(egui::vec2(EVAL_BAR_WIDTH - 20.0, BOARD_SIZE - 40.0),
+                                                                   egui::Sense::hover());
+                    let mut left = rect;
+                    left.set_top(rect.bottom() - rect.height() * self.evaluation_shown.map_or(0.5, |e| e.left_share()));
+                    ui.painter().rect_filled(rect, 2.0, PLAYER2_BAR_COLOR);
+                    ui.painter().rect_filled(left, 2.0, PLAYER1_BAR_COLOR);
+                    response.on_hover_text("Expected result, Player 1's final score minus Player 2's");
+            });
+        }
+
         // Bottom panel contains turn information and start/reset buttons
         egui::TopBottomPanel::bottom("bottom").show_separator_line(false).show(ctx, |ui| {
             ui.horizontal(|ui| {
-                if self.game.state == State::Playing {
+                if self.editor.is_some() {
+                    self.editor_controls(ui);
+                }
+                else if self.game.state == State::Playing {
                     ui.label(format!("Turn: {}", match self.game.turn {
                         Turn::Left => "Player 1",
                         Turn::Right => "Player 2"
@@ -199,58 +1179,222 @@ impl eframe::App for GameInterface {
                         State::LeftWin => "Player 1 Wins!",
                         State::RightWin => "Player 2 Wins!",
                         State::Draw => "Tie Game",
+                        State::TimeOut(Turn::Left) => "Player 1 ran out of time. Player 2 Wins!",
+                        State::TimeOut(Turn::Right) => "Player 2 ran out of time. Player 1 Wins!",
+                        State::Resigned(Turn::Left) => "Player 1 resigned. Player 2 Wins!",
+                        State::Resigned(Turn::Right) => "Player 2 resigned. Player 1 Wins!",
+                        State::DrawAgreed => "Draw agreed",
                         _ => ""
                     });
                 }
+                if let (None, Some((_, _, Some(thinking)))) = (&self.recording, &self.thinking) {
+                    match thinking.progress() {
+                        Some(progress) => {
+                            ui.add(egui::ProgressBar::new(progress).desired_width(120.0).text("Thinking…"));
+                        },
+                        None => {
+                            ui.add(egui::Spinner::new());
+                            ui.label("Thinking…");
+                        }
+                    }
+                }
+                // During a replay, comment on the move just shown
+                let moves_shown = self.game.recording.moves.len();
+                if let Some(m) = self.analysis.as_mut().and_then(Background::get)
+                    .and_then(|a| a.moves.get(moves_shown.wrapping_sub(1))) {
+                    let comment = m.comment(self.game.get_board_size());
+                    ui.label(if comment.is_empty() { "Good move".to_string() } else { comment });
+                }
+                if let Some(m) = self.game.recording.moves.last().filter(|_| self.editor.is_none()) {
+                    ui.label(format!("Last: {}", m.to_notation(self.game.get_board_size())));
+                }
+                if !self.network_status.is_empty() {
+                    ui.label(&self.network_status);
+                }
+                if let Some(count) = self.session.as_ref().map(|s| s.spectator_count()).filter(|c| *c > 0) {
+                    ui.label(format!("Spectators: {}", count));
+                }
+                self.game_actions(ui);
+                self.puzzle_controls(ui);
+                if self.hint_allowed() && self.puzzle.is_none() && ui.button("Hint").clicked() {
+                    let game = self.game.clone();
+                    self.hint = Some((self.game.recording.moves.len(),
+                                      Background::start(move || Engine::new(HINT_DEPTH).hint(&game))));
+                }
+                if let Some(hint) = self.hint.as_mut().and_then(|(_, hint)| hint.get()).and_then(Option::as_ref) {
+                    let letter = match hint.suggestion.cell {
+                        Cell::O => "O",
+                        _ => "S"
+                    };
+                    ui.label(match hint.reason {
+                        HintReason::Scores(1) => format!("{} completes an SOS", letter),
+                        HintReason::Scores(n) => format!("{} completes {} SOSs", letter, n),
+                        HintReason::Blocks => format!("{} stops an SOS the opponent could complete", letter),
+                        HintReason::Safe => format!("{} is safe", letter),
+                        HintReason::LeastBad => format!("{} gives away the least", letter)
+                    });
+                }
+                if let Some(notation) = self.position_notation() {
+                    if ui.button("Copy").on_hover_text("Copy the position as text; paste one to set it up").clicked() {
+                        ui.output_mut(|o| o.copied_text = notation);
+                    }
+                }
+                if self.undo_allowed() {
+                    if ui.add_enabled(self.game.can_undo(), egui::Button::new("Undo")).clicked() {
+                        self.undo();
+                    }
+                    if ui.add_enabled(self.game.can_redo(), egui::Button::new("Redo")).clicked() {
+                        self.redo();
+                    }
+                }
             });
         });
 
+        // Copying and pasting positions, unless a text field has the keyboard
+        if ctx.memory(|m| m.focus().is_none()) {
+            let (copied, pasted) = ctx.input(|i| (
+                i.events.contains(&egui::Event::Copy),
+                i.events.iter().find_map(|e| match e {
+                    egui::Event::Paste(text) => Some(text.clone()),
+                    _ => None
+                })
+            ));
+            if let Some(notation) = self.position_notation().filter(|_| copied) {
+                ctx.output_mut(|o| o.copied_text = notation);
+            }
+            if let Some(text) = pasted {
+                self.paste(&text);
+            }
+        }
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+            self.undo();
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y)) {
+            self.redo();
+        }
+
+        if let Some(clock) = &mut self.clock {
+            clock.update(&mut self.game, Instant::now());
+            // Keep the clocks ticking without waiting for mouse movement
+            if self.game.state == State::Playing {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+
+        if self.update_puzzle() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        if self.update_evaluation() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        let board_open = self.board_open();
         let current_turn = match self.game.turn {
-            Turn::Left => &self.player1,
-            Turn::Right => &self.player2
+            Turn::Left => &mut self.player1,
+            Turn::Right => &mut self.player2
         };
 
         if current_turn.computer && self.game.state == State::Playing {
+            // Any change of position, e.g. an undo, starts the turn over
+            let hash = self.game.hash();
+            if self.thinking.as_ref().is_none_or(|(thinking_hash, ..)| *thinking_hash != hash) {
+                self.thinking = Some((hash, Instant::now(), None));
+            }
+            let (_, started, pending) = self.thinking.as_mut().unwrap();
+            let delay_over = started.elapsed().as_secs_f32() >= self.move_delay;
             match &mut self.recording {
-                None => self.game.make_random_move(),
-                Some(recording) => {
-                    let next_move = recording.next_move();
-                    match next_move {
-                        // Somehow the recording ended before the game was finished
-                        None => self.game.state = State::Draw,
-                        Some(m) => {
-                            self.game.make_move(m.cell, m.row, m.col);
-                            thread::sleep(Duration::from_millis(1500));
+                None => {
+                    let pending = pending.get_or_insert_with(|| current_turn.start_move(&self.game, &self.solver, &self.book));
+                    if delay_over {
+                        if let Some(best) = current_turn.poll_move(pending, &self.game) {
+                            self.thinking = None;
+                            match best {
+                                Some(m) => self.game.make_move(m.cell, m.row, m.col),
+                                None => self.game.make_random_move()
+                            }
                         }
                     }
+                },
+                Some(recording) => if delay_over {
+                    self.thinking = None;
+                    match recording.next_move() {
+                        // Recordings of games ended early say how; otherwise call it a draw
+                        None => self.game.state = recording.ending.unwrap_or(State::Draw),
+                        Some(m) => self.game.make_move(m.cell, m.row, m.col)
+                    }
                 }
             }
-            ctx.request_repaint(); // otherwise, requires mouse movement
+            // Keep checking on the move without waiting for mouse movement
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        // The editor's board follows the size slider
+        if let Some(editor) = self.editor.as_mut().filter(|e| e.setup.board.size() != self.next_board_size) {
+            editor.setup.board = Bitboard::new(self.next_board_size);
         }
 
         // Central panel contains game board
         egui::CentralPanel::default().show(ctx, |ui| {
+            let board_size = self.editor.as_ref().map_or(self.game.get_board_size(), |e| e.setup.board.size());
             // button_size = measured board size / unit board size - button padding
-            let button_size = BOARD_SIZE / self.game.get_board_size() as f32 - 8.0;
+            let button_size = BOARD_SIZE / board_size as f32 - 8.0;
             let style = ui.style_mut();
             style.text_styles.insert(TextStyle::Button, FontId::new(button_size * 0.75, FontFamily::Proportional));
 
-            for y in 0..self.game.get_board_size() {
+            let hinted = self.hint.as_mut().and_then(|(_, hint)| hint.get()).and_then(Option::as_ref)
+                .map(|hint| hint.suggestion.clone());
+            let show_threats = self.show_threats && self.game.state == State::Playing && !current_turn.computer;
+            for y in 0..board_size {
                 ui.horizontal(|ui| {
-                    for x in 0..self.game.get_board_size() {
-                        if ui.add(egui::Button::new(match self.game.get_cell(x, y).unwrap() {
+                    for x in 0..board_size {
+                        let cell = match &self.editor {
+                            Some(editor) => editor.setup.board.get(y, x),
+                            None => *self.game.get_cell(x, y).unwrap()
+                        };
+                        let mut button = egui::Button::new(match cell {
                             Cell::Empty => "",
                             Cell::O => "O",
                             Cell::S => "S"
-                            // The minimum size below is used so the buttons don't scale differently between letters
-                        }).min_size(egui::vec2(button_size, button_size))).clicked()
+                        });
+                        if show_threats {
+                            match self.game.threat(current_turn.pmove, y, x) {
+                                Threat::Completes => button = button.fill(COMPLETES_COLOR),
+                                Threat::GivesAway => button = button.fill(GIVES_AWAY_COLOR),
+                                Threat::None => ()
+                            }
+                        }
+                        if let Some(m) = hinted.as_ref().filter(|m| m.row == y && m.col == x) {
+                            button = egui::Button::new(RichText::new(match m.cell {
+                                Cell::O => "O",
+                                _ => "S"
+                            }).color(Color32::WHITE)).fill(HINT_COLOR);
+                        }
+                        // The minimum size below is used so the buttons don't scale differently between letters
+                        let clicked = ui.add(button.min_size(egui::vec2(button_size, button_size))).clicked();
+                        if let Some(editor) = self.editor.as_mut().filter(|_| clicked) {
+                            // Each click moves the cell on through S, O and back to empty
+                            editor.setup.board.set(y, x, match cell {
+                                Cell::Empty => Cell::S,
+                                Cell::S => Cell::O,
+                                Cell::O => Cell::Empty
+                            });
+                            editor.decided = false;
+                        } else if clicked
+                            && board_open
                             && self.game.state == State::Playing
                             && !current_turn.computer {
-                            self.game.make_move(current_turn.pmove.clone(), y, x);
+                            match &mut self.session {
+                                Some(session) => session.submit_move(&mut self.game, Move { cell: current_turn.pmove, row: y, col: x }),
+                                None => self.game.make_move(current_turn.pmove, y, x)
+                            }
                         }
                     }
                 });
             }
         });
+
+        self.record_result();
     }
 }
@@ -0,0 +1,712 @@
+//!
+//! Playing a game between two machines over TCP
+//!
+//! The host owns the real `Game`. The guest sends move requests, and the host validates them
+//! with `make_move` and sends every accepted move back with the resulting turn, scores and state.
+//! Spectators connect the same way but only receive the moves played so far and every move after.
+//! Either player may resign, or offer a draw by sending `DRAW`; a draw is agreed when the other
+//! player sends `DRAW` back before the next move. Network games have no clock, so no turn is
+//! ever given up on time.
+//! Messages are single lines of text, starting with a keyword.
+//!
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::bitboard::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::game::Game;
+use crate::game_enums::{Mode, State, Turn};
+use crate::notation::{cell_string, mode_string, parse_cell, parse_mode, parse_state, parse_turn, state_string, turn_string};
+use crate::recording::Move;
+
+/// Bumped whenever the meaning of a message changes
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7878;
+/// How long a new connection has to say hello before it is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// First line sent by a guest, or by a spectator when `watch` is set
+    Hello { version: u32, watch: bool },
+    /// Host's reply to a compatible guest, describing the game
+    Welcome { version: u32, mode: Mode, board_size: usize },
+    /// A move request from the guest, or a move accepted by the host
+    Move(Move),
+    /// The host's view of the game after a move
+    Update { turn: Turn, left_score: u32, right_score: u32, state: State },
+    /// The sender gives up the game
+    Resign,
+    /// The sender offers a draw, or accepts the other player's offer
+    Draw,
+    Error(String),
+    Bye
+}
+
+impl Message {
+    pub fn as_string(&self) -> String {
+        match self {
+            Message::Hello { version, watch: false } => format!("HELLO {}", version),
+            Message::Hello { version, watch: true } => format!("HELLO {} WATCH", version),
+            Message::Welcome { version, mode, board_size } =>
+                format!("WELCOME {} {} {}", version, mode_string(mode), board_size),
+            Message::Move(m) => format!("MOVE {} {} {}", cell_string(m.cell), m.row, m.col),
+            Message::Update { turn, left_score, right_score, state } =>
+                format!("UPDATE {} {} {} {}", turn_string(*turn), left_score, right_score, state_string(*state)),
+            Message::Resign => "RESIGN".to_string(),
+            Message::Draw => "DRAW".to_string(),
+            Message::Error(reason) => format!("ERROR {}", reason),
+            Message::Bye => "BYE".to_string()
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["HELLO", version] => Some(Message::Hello { version: version.parse().ok()?, watch: false }),
+            ["HELLO", version, "WATCH"] => Some(Message::Hello { version: version.parse().ok()?, watch: true }),
+            ["WELCOME", version, mode, board_size] => Some(Message::Welcome {
+                version: version.parse().ok()?,
+                mode: parse_mode(mode)?,
+                board_size: board_size.parse().ok()
+                    .filter(|size| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(size))?
+            }),
+            ["MOVE", cell, row, col] => Some(Message::Move(Move {
+                cell: parse_cell(cell)?,
+                row: row.parse().ok()?,
+                col: col.parse().ok()?
+            })),
+            ["UPDATE", turn, left_score, right_score, state] => Some(Message::Update {
+                turn: parse_turn(turn)?,
+                left_score: left_score.parse().ok()?,
+                right_score: right_score.parse().ok()?,
+                state: parse_state(state)?
+            }),
+            ["ERROR", ..] => Some(Message::Error(line.trim()["ERROR".len()..].trim().to_string())),
+            ["RESIGN"] => Some(Message::Resign),
+            ["DRAW"] => Some(Message::Draw),
+            ["BYE"] => Some(Message::Bye),
+            _ => None
+        }
+    }
+
+    /// The host's current view of a game
+    pub fn update(game: &Game) -> Self {
+        Message::Update {
+            turn: game.turn,
+            left_score: game.left_score,
+            right_score: game.right_score,
+            state: game.state
+        }
+    }
+}
+
+/// A line-based connection whose incoming messages are read on a background thread
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Message>
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(..) => break
+                };
+                // Unknown lines are ignored so newer peers can add messages
+                if let Some(message) = Message::parse(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { stream, incoming })
+    }
+
+    pub fn connect(address: &str) -> std::io::Result<Self> {
+        let address = address.to_socket_addrs()?.next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address"))?;
+        Self::new(TcpStream::connect_timeout(&address, Duration::from_secs(5))?)
+    }
+
+    pub fn send(&mut self, message: &Message) -> std::io::Result<()> {
+        self.stream.write_all((message.as_string() + "\n").as_bytes())
+    }
+
+    /// Next message if one has arrived, or Err(Disconnected) once the peer has gone
+    pub fn try_recv(&self) -> Result<Message, TryRecvError> {
+        self.incoming.try_recv()
+    }
+
+    /// Wait for the next message, giving up after `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Message> {
+        self.incoming.recv_timeout(timeout).ok()
+    }
+}
+
+/// What happened while polling a session
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A guest joined, or the host accepted us; the game is ready to play
+    Started,
+    /// The host rejected something
+    Error(String),
+    /// The other player offered a draw
+    DrawOffered,
+    Disconnected
+}
+
+/// Which part this machine plays in a networked game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role { Host, Guest, Spectator }
+
+/// One side of a networked game
+pub struct Session {
+    pub role: Role,
+    /// The host plays Player 1 and the guest Player 2
+    pub local_side: Turn,
+    listener: Option<TcpListener>,
+    /// The host for guests and spectators, or the guest for the host
+    peer: Option<Connection>,
+    /// Read-only connections kept by the host
+    spectators: Vec<Connection>,
+    /// Connections the host has accepted that have not said hello yet, with when they arrived
+    pending: Vec<(Connection, Instant)>,
+    /// Player with a draw offer standing, and the number of moves when it was made
+    draw_offer: Option<(Turn, usize)>
+}
+
+impl Session {
+    /// Start listening for a guest and spectators
+    ///
+    /// The guest is only told the mode and board size, so the host's game must start from an
+    /// empty board with no clock
+    pub fn host(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self::new(Role::Host, Turn::Left, Some(listener), None))
+    }
+
+    /// Connect to a host and ask to play
+    pub fn join(address: &str) -> std::io::Result<Self> {
+        let mut peer = Connection::connect(address)?;
+        peer.send(&Message::Hello { version: PROTOCOL_VERSION, watch: false })?;
+        Ok(Self::new(Role::Guest, Turn::Right, None, Some(peer)))
+    }
+
+    /// Connect to a host and follow its game without playing
+    pub fn watch(address: &str) -> std::io::Result<Self> {
+        let mut peer = Connection::connect(address)?;
+        peer.send(&Message::Hello { version: PROTOCOL_VERSION, watch: true })?;
+        Ok(Self::new(Role::Spectator, Turn::Right, None, Some(peer)))
+    }
+
+    fn new(role: Role, local_side: Turn, listener: Option<TcpListener>, peer: Option<Connection>) -> Self {
+        Self { role, local_side, listener, peer, spectators: Vec::new(), pending: Vec::new(), draw_offer: None }
+    }
+
+    /// Player whose draw offer still stands; offers lapse once another move is made
+    pub fn draw_offer(&self, game: &Game) -> Option<Turn> {
+        self.draw_offer
+            .filter(|(_, moves)| *moves == game.recording.moves.len() && game.state == State::Playing)
+            .map(|(side, _)| side)
+    }
+
+    /// Give up the game on behalf of the local player
+    pub fn resign(&mut self, game: &mut Game) {
+        if self.role == Role::Spectator || game.state != State::Playing {
+            return;
+        }
+        if self.is_host() {
+            game.resign(self.local_side);
+            self.send_all(&Message::update(game));
+        } else {
+            self.send(&Message::Resign);
+        }
+    }
+
+    /// Offer a draw, or accept the other player's offer
+    pub fn offer_draw(&mut self, game: &mut Game) {
+        if self.role == Role::Spectator || game.state != State::Playing {
+            return;
+        }
+        self.send(&Message::Draw);
+        if self.draw_offer(game).is_some_and(|side| side != self.local_side) {
+            if self.is_host() {
+                game.agree_draw();
+                self.send_all(&Message::update(game));
+            }
+        } else {
+            self.draw_offer = Some((self.local_side, game.recording.moves.len()));
+        }
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.role == Role::Host
+    }
+
+    /// Whether the player on turn is at this machine
+    pub fn is_local_turn(&self, game: &Game) -> bool {
+        self.role != Role::Spectator && game.turn == self.local_side
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len()
+    }
+
+    pub fn local_address(&self) -> Option<String> {
+        self.listener.as_ref()?.local_addr().ok().map(|a| a.to_string())
+    }
+
+    /// Play a move chosen on this machine
+    ///
+    /// The host applies it straight away; the guest waits for the host to accept it
+    pub fn submit_move(&mut self, game: &mut Game, m: Move) {
+        if !self.is_local_turn(game) {
+            return;
+        }
+        if self.is_host() {
+            let moves_before = game.recording.moves.len();
+            game.make_move(m.cell, m.row, m.col);
+            if game.recording.moves.len() > moves_before {
+                self.broadcast(game, m);
+            }
+        } else if let Some(peer) = &mut self.peer {
+            let _ = peer.send(&Message::Move(m));
+        }
+    }
+
+    /// Handle everything that has arrived since the last call
+    ///
+    /// A guest's or spectator's `game` is replaced when the host welcomes it
+    pub fn poll(&mut self, game: &mut Game) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.is_host() {
+            self.accept(game, &mut events);
+            self.poll_spectators();
+        }
+
+        loop {
+            let message = match &self.peer {
+                None => break,
+                Some(peer) => match peer.try_recv() {
+                    Ok(message) => message,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.peer = None;
+                        events.push(Event::Disconnected);
+                        break;
+                    }
+                }
+            };
+            if self.is_host() {
+                self.host_receive(game, message, &mut events);
+            } else {
+                self.guest_receive(game, message, &mut events);
+            }
+        }
+        events
+    }
+
+    /// Tell everyone connected that we are leaving
+    pub fn close(&mut self) {
+        self.send_all(&Message::Bye);
+        self.peer = None;
+        self.spectators.clear();
+        self.pending.clear();
+    }
+
+    /// Take in waiting connections, and seat those that have said hello as the guest or a spectator
+    ///
+    /// Nothing here waits on the network: a connection that has not said hello yet is looked at
+    /// again on the next poll.
+    fn accept(&mut self, game: &mut Game, events: &mut Vec<Event>) {
+        while let Some(Ok((stream, _))) = self.listener.as_ref().map(|l| l.accept()) {
+            if let Ok(peer) = stream.set_nonblocking(false).and_then(|_| Connection::new(stream)) {
+                self.pending.push((peer, Instant::now()));
+            }
+        }
+
+        for (peer, arrived) in std::mem::take(&mut self.pending) {
+            match peer.try_recv() {
+                Ok(message) => self.greet(game, peer, message, events),
+                Err(TryRecvError::Empty) if arrived.elapsed() < HANDSHAKE_TIMEOUT => self.pending.push((peer, arrived)),
+                Err(..) => ()
+            }
+        }
+    }
+
+    /// Answer the first message from a new connection
+    fn greet(&mut self, game: &mut Game, mut peer: Connection, message: Message, events: &mut Vec<Event>) {
+        let welcome = Message::Welcome {
+            version: PROTOCOL_VERSION,
+            mode: game.get_mode(),
+            board_size: game.get_board_size()
+        };
+        match message {
+            Message::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                let _ = peer.send(&Message::Error(format!("unsupported protocol version {}", version)));
+            },
+            Message::Hello { watch: true, .. } if catch_up(&mut peer, game, &welcome) => {
+                self.spectators.push(peer);
+            },
+            Message::Hello { watch: false, .. } if self.peer.is_some() => {
+                let _ = peer.send(&Message::Error("game is full".to_string()));
+            },
+            Message::Hello { watch: false, .. } => {
+                // The host's game goes on, so a guest who rejoins picks up the moves made so far
+                let state = game.state;
+                if state == State::NotStarted {
+                    game.state = State::Playing;
+                }
+                if catch_up(&mut peer, game, &welcome) {
+                    self.peer = Some(peer);
+                    for spectator in &mut self.spectators {
+                        let _ = spectator.send(&Message::update(game));
+                    }
+                    events.push(Event::Started);
+                } else {
+                    game.state = state;
+                }
+            },
+            _ => ()
+        }
+    }
+
+    /// Drop spectators who have left, and turn away any moves they send
+    fn poll_spectators(&mut self) {
+        self.spectators.retain_mut(|spectator| loop {
+            match spectator.try_recv() {
+                Ok(Message::Bye) | Err(TryRecvError::Disconnected) => break false,
+                Ok(Message::Move(_)) => {
+                    let _ = spectator.send(&Message::Error("spectators cannot move".to_string()));
+                },
+                Ok(_) => (),
+                Err(TryRecvError::Empty) => break true
+            }
+        });
+    }
+
+    fn host_receive(&mut self, game: &mut Game, message: Message, events: &mut Vec<Event>) {
+        match message {
+            Message::Move(m) => {
+                let moves_before = game.recording.moves.len();
+                if game.turn == self.local_side || game.state != State::Playing {
+                    self.send(&Message::Error("not your turn".to_string()));
+                    return;
+                }
+                game.make_move(m.cell, m.row, m.col);
+                if game.recording.moves.len() > moves_before {
+                    self.broadcast(game, m);
+                } else {
+                    self.send(&Message::Error("illegal move".to_string()));
+                }
+            },
+            Message::Resign if game.state == State::Playing => {
+                game.resign(remote_side(self.local_side));
+                self.send_all(&Message::update(game));
+            },
+            Message::Draw if game.state == State::Playing => {
+                if self.draw_offer(game) == Some(self.local_side) {
+                    game.agree_draw();
+                    self.send_all(&Message::update(game));
+                } else {
+                    self.draw_offer = Some((remote_side(self.local_side), game.recording.moves.len()));
+                    events.push(Event::DrawOffered);
+                }
+            },
+            Message::Bye => {
+                self.peer = None;
+                events.push(Event::Disconnected);
+            },
+            _ => ()
+        }
+    }
+
+    fn guest_receive(&mut self, game: &mut Game, message: Message, events: &mut Vec<Event>) {
+        match message {
+            Message::Welcome { mode, board_size, .. } => {
+                *game = Game::new(mode, board_size);
+                game.state = State::Playing;
+                events.push(Event::Started);
+            },
+            // Scores, turn and state are corrected by the update that follows
+            Message::Move(m) => game.make_move(m.cell, m.row, m.col),
+            Message::Update { turn, left_score, right_score, state } => {
+                // The first update of a game tells us who moves first
+                game.set_first_turn(turn);
+                game.turn = turn;
+                game.left_score = left_score;
+                game.right_score = right_score;
+                // Games ended early are kept in the recording, as they are on the host
+                game.end(state);
+                game.state = state;
+            },
+            Message::Draw => {
+                self.draw_offer = Some((remote_side(self.local_side), game.recording.moves.len()));
+                events.push(Event::DrawOffered);
+            },
+            Message::Error(reason) => events.push(Event::Error(reason)),
+            Message::Bye => {
+                self.peer = None;
+                events.push(Event::Disconnected);
+            },
+            Message::Hello { .. } | Message::Resign => ()
+        }
+    }
+
+    /// Send an accepted move and the resulting game state to the guest and spectators
+    fn broadcast(&mut self, game: &Game, m: Move) {
+        self.send_all(&Message::Move(m));
+        self.send_all(&Message::update(game));
+    }
+
+    fn send(&mut self, message: &Message) {
+        if let Some(peer) = &mut self.peer {
+            let _ = peer.send(message);
+        }
+    }
+
+    fn send_all(&mut self, message: &Message) {
+        self.send(message);
+        for spectator in &mut self.spectators {
+            let _ = spectator.send(message);
+        }
+    }
+}
+
+/// Send a new guest or spectator the game so far, replayed from the right first player
+///
+/// Returns false if the connection was lost on the way
+fn catch_up(peer: &mut Connection, game: &Game, welcome: &Message) -> bool {
+    let start = Game::at_start(&game.recording);
+    let mut history = vec![welcome.clone(), Message::update(&start)];
+    history.extend(game.recording.moves.iter().cloned().map(Message::Move));
+    history.push(Message::update(game));
+    history.iter().all(|message| peer.send(message).is_ok())
+}
+
+fn remote_side(local_side: Turn) -> Turn {
+    match local_side {
+        Turn::Left => Turn::Right,
+        Turn::Right => Turn::Left
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::Cell;
+
+    /// Poll both sides until `done` holds or a couple of seconds pass
+    fn poll_until(host: &mut Session, host_game: &mut Game, guest: &mut Session, guest_game: &mut Game,
+                  done: impl Fn(&Game, &Game) -> bool) {
+        for _ in 0..200 {
+            host.poll(host_game);
+            guest.poll(guest_game);
+            if done(host_game, guest_game) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Hello { version: 1, watch: false },
+            Message::Hello { version: 1, watch: true },
+            Message::Welcome { version: 1, mode: Mode::Simple, board_size: 7 },
+            Message::Move(Move { cell: Cell::O, row: 3, col: 4 }),
+            Message::Update { turn: Turn::Right, left_score: 2, right_score: 5, state: State::RightWin },
+            Message::Update { turn: Turn::Left, left_score: 0, right_score: 1, state: State::Resigned(Turn::Left) },
+            Message::Resign,
+            Message::Draw,
+            Message::Error("not your turn".to_string()),
+            Message::Bye
+        ];
+        for message in messages {
+            assert_eq!(Message::parse(&message.as_string()), Some(message));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown_lines() {
+        assert_eq!(Message::parse("MOVE X 1 2"), None);
+        assert_eq!(Message::parse("DANCE"), None);
+        assert_eq!(Message::parse(&format!("WELCOME 1 C {}", MAX_BOARD_SIZE + 1)), None);
+        assert_eq!(Message::parse("WELCOME 1 C 2"), None);
+    }
+
+    #[test]
+    fn guest_moves_are_validated_by_host() {
+        let mut host_game = Game::new(Mode::Classic, 4);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let mut guest_game = Game::new(Mode::Simple, 9);
+        let mut guest = Session::join(&address).unwrap();
+
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+        assert_eq!(guest_game.get_board_size(), 4);
+
+        // Out of turn, so the host rejects it
+        guest.local_side = Turn::Left;
+        guest.submit_move(&mut guest_game, Move { cell: Cell::S, row: 0, col: 0 });
+        guest.local_side = Turn::Right;
+        let mut rejected = false;
+        for _ in 0..200 {
+            host.poll(&mut host_game);
+            if guest.poll(&mut guest_game).contains(&Event::Error("not your turn".to_string())) {
+                rejected = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(rejected);
+
+        host.submit_move(&mut host_game, Move { cell: Cell::S, row: 1, col: 1 });
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |_, g| g.turn == Turn::Right);
+        guest.submit_move(&mut guest_game, Move { cell: Cell::O, row: 2, col: 2 });
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.recording.moves.len() == 2 && g.recording.moves.len() == 2);
+
+        assert_eq!(host_game.get_cell(0, 0), Ok(&Cell::Empty));
+        assert_eq!(guest_game.get_cell(2, 2), Ok(&Cell::O));
+        assert_eq!(guest_game.turn, Turn::Left);
+    }
+
+    #[test]
+    fn silent_connections_do_not_hold_up_the_host() {
+        let mut host_game = Game::new(Mode::Classic, 4);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let _silent = TcpStream::connect(&address).unwrap();
+        let mut guest_game = Game::new(Mode::Classic, 4);
+        let mut guest = Session::join(&address).unwrap();
+
+        let started = Instant::now();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+        assert!(started.elapsed() < HANDSHAKE_TIMEOUT);
+        assert_eq!(guest_game.state, State::Playing);
+    }
+
+    #[test]
+    fn rejoining_guest_picks_up_the_hosts_game() {
+        let mut host_game = Game::new(Mode::Classic, 4);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let mut guest_game = Game::new(Mode::Classic, 4);
+        let mut guest = Session::join(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+        host.submit_move(&mut host_game, Move { cell: Cell::S, row: 0, col: 0 });
+        guest.close();
+        for _ in 0..200 {
+            if host.poll(&mut host_game).contains(&Event::Disconnected) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut guest_game = Game::new(Mode::Simple, 3);
+        let mut guest = Session::join(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |_, g| g.recording.moves.len() == 1 && g.turn == Turn::Right);
+        assert_eq!(host_game.recording.moves.len(), 1);
+        assert_eq!(guest_game.get_cell(0, 0), Ok(&Cell::S));
+        assert_eq!(guest_game.turn, Turn::Right);
+        assert!(guest.is_local_turn(&guest_game));
+    }
+
+    #[test]
+    fn spectator_catches_up_and_cannot_move() {
+        let mut host_game = Game::new(Mode::Classic, 4);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let mut guest_game = Game::new(Mode::Classic, 4);
+        let mut guest = Session::join(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+        host.submit_move(&mut host_game, Move { cell: Cell::S, row: 0, col: 0 });
+
+        let mut spectator_game = Game::new(Mode::Simple, 3);
+        let mut spectator = Session::watch(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut spectator, &mut spectator_game,
+                   |_, s| s.recording.moves.len() == 1);
+        assert_eq!(host.spectator_count(), 1);
+        assert_eq!(spectator_game.get_cell(0, 0), Ok(&Cell::S));
+        assert_eq!(spectator_game.turn, Turn::Right);
+
+        // Moves from a spectator never reach the game
+        spectator.role = Role::Guest;
+        spectator.submit_move(&mut spectator_game, Move { cell: Cell::O, row: 3, col: 3 });
+        spectator.role = Role::Spectator;
+        assert!(!spectator.is_local_turn(&spectator_game));
+
+        guest.submit_move(&mut guest_game, Move { cell: Cell::O, row: 1, col: 1 });
+        for _ in 0..200 {
+            host.poll(&mut host_game);
+            guest.poll(&mut guest_game);
+            spectator.poll(&mut spectator_game);
+            if spectator_game.recording.moves.len() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(spectator_game.recording.moves, host_game.recording.moves);
+        assert_eq!(host_game.get_cell(3, 3), Ok(&Cell::Empty));
+    }
+
+    #[test]
+    fn draw_is_agreed_when_both_offer() {
+        let mut host_game = Game::new(Mode::Simple, 3);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let mut guest_game = Game::new(Mode::Simple, 3);
+        let mut guest = Session::join(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+
+        guest.offer_draw(&mut guest_game);
+        for _ in 0..200 {
+            if host.poll(&mut host_game).contains(&Event::DrawOffered) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(host.draw_offer(&host_game), Some(Turn::Right));
+
+        host.offer_draw(&mut host_game);
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |_, g| g.state == State::DrawAgreed);
+        assert_eq!(host_game.state, State::DrawAgreed);
+        assert_eq!(guest_game.recording.ending, Some(State::DrawAgreed));
+    }
+
+    #[test]
+    fn guest_resignation_ends_the_game() {
+        let mut host_game = Game::new(Mode::Classic, 3);
+        let mut host = Session::host("127.0.0.1:0").unwrap();
+        let address = host.local_address().unwrap();
+        let mut guest_game = Game::new(Mode::Classic, 3);
+        let mut guest = Session::join(&address).unwrap();
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |h, g| h.state == State::Playing && g.state == State::Playing);
+
+        guest.resign(&mut guest_game);
+        poll_until(&mut host, &mut host_game, &mut guest, &mut guest_game,
+                   |_, g| g.state.is_over());
+        assert_eq!(host_game.state, State::Resigned(Turn::Right));
+        assert_eq!(guest_game.state.winner(), Some(Turn::Left));
+    }
+}
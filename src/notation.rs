@@ -0,0 +1,122 @@
+//!
+//! Text forms of cells, turns, modes, states and boards
+//!
+//! Shared by recordings, position notation, the network protocol and the bot protocol.
+//!
+
+use crate::bitboard::{Bitboard, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::game::Game;
+use crate::game_enums::{Cell, Mode, State, Turn};
+
+/// Board rows from the top separated by `/`, with `-` for an empty cell
+pub fn rows_string(game: &Game) -> String {
+    board_string(game.bitboard())
+}
+
+/// Rows of a board in the same form as `rows_string`
+pub fn board_string(board: &Bitboard) -> String {
+    let rows: Vec<String> = board.rows().into_iter()
+        .map(|row| row.into_iter().map(cell_string).collect())
+        .collect();
+    rows.join("/")
+}
+
+/// Read rows written by `board_string`, which must make a square board of a size the game
+/// supports
+pub fn parse_board(s: &str) -> Option<Bitboard> {
+    let rows: Vec<&str> = s.split('/').collect();
+    let size = rows.len();
+    if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+        return None;
+    }
+    let mut board = Bitboard::new(size);
+    for (row, letters) in rows.iter().enumerate() {
+        if letters.chars().count() != size {
+            return None;
+        }
+        for (col, letter) in letters.chars().enumerate() {
+            match letter {
+                '-' => (),
+                _ => board.set(row, col, parse_cell(&letter.to_string())?)
+            }
+        }
+    }
+    Some(board)
+}
+
+pub fn mode_string(mode: &Mode) -> &'static str {
+    match mode {
+        Mode::Classic => "C",
+        Mode::Simple => "S"
+    }
+}
+
+pub fn parse_mode(s: &str) -> Option<Mode> {
+    match s {
+        "C" => Some(Mode::Classic),
+        "S" => Some(Mode::Simple),
+        _ => None
+    }
+}
+
+pub fn cell_string(cell: Cell) -> &'static str {
+    match cell {
+        Cell::S => "S",
+        Cell::O => "O",
+        Cell::Empty => "-"
+    }
+}
+
+pub fn parse_cell(s: &str) -> Option<Cell> {
+    match s {
+        "S" => Some(Cell::S),
+        "O" => Some(Cell::O),
+        _ => None
+    }
+}
+
+pub fn turn_string(turn: Turn) -> &'static str {
+    match turn {
+        Turn::Left => "L",
+        Turn::Right => "R"
+    }
+}
+
+pub fn parse_turn(s: &str) -> Option<Turn> {
+    match s {
+        "L" => Some(Turn::Left),
+        "R" => Some(Turn::Right),
+        _ => None
+    }
+}
+
+pub fn state_string(state: State) -> &'static str {
+    match state {
+        State::LeftWin => "LEFTWIN",
+        State::RightWin => "RIGHTWIN",
+        State::Draw => "DRAW",
+        State::Playing => "PLAYING",
+        State::NotStarted => "NOTSTARTED",
+        State::TimeOut(Turn::Left) => "LEFTTIMEOUT",
+        State::TimeOut(Turn::Right) => "RIGHTTIMEOUT",
+        State::Resigned(Turn::Left) => "LEFTRESIGNED",
+        State::Resigned(Turn::Right) => "RIGHTRESIGNED",
+        State::DrawAgreed => "DRAWAGREED"
+    }
+}
+
+pub fn parse_state(s: &str) -> Option<State> {
+    match s {
+        "LEFTWIN" => Some(State::LeftWin),
+        "RIGHTWIN" => Some(State::RightWin),
+        "DRAW" => Some(State::Draw),
+        "PLAYING" => Some(State::Playing),
+        "NOTSTARTED" => Some(State::NotStarted),
+        "LEFTTIMEOUT" => Some(State::TimeOut(Turn::Left)),
+        "RIGHTTIMEOUT" => Some(State::TimeOut(Turn::Right)),
+        "LEFTRESIGNED" => Some(State::Resigned(Turn::Left)),
+        "RIGHTRESIGNED" => Some(State::Resigned(Turn::Right)),
+        "DRAWAGREED" => Some(State::DrawAgreed),
+        _ => None
+    }
+}
@@ -1,16 +1,241 @@
-use crate::game_enums::Cell;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use crate::book::OpeningBook;
+use crate::bot::ExternalBot;
+use crate::game::Game;
+use crate::game_enums::{Cell, Strategy};
+use crate::recording::Move;
+use crate::search::{default_threads, Search, SearchStatus, Searcher};
+use crate::solver::Solver;
+
+/// How many letters ahead the Engine strategy looks
+pub const ENGINE_DEPTH: u32 = 3;
+
+/// A computer player's move on its way
+pub enum Thinking {
+    /// Worked out already; None means play a random move
+    Ready(Option<Move>),
+    Searching(Search),
+    /// Waiting for the external bot to start, with the command it is being started with
+    LaunchingBot(String, Receiver<std::io::Result<ExternalBot>>),
+    /// Waiting for the external bot to answer
+    AskingBot
+}
+
+impl Thinking {
+    /// How far a search has got, from 0 to 1, if the move is being searched for
+    pub fn progress(&self) -> Option<f32> {
+        match self {
+            Thinking::Searching(search) => Some(search.progress()),
+            _ => None
+        }
+    }
+}
 
-#[derive(Clone)]
 pub struct Player {
+    /// Name ratings are kept under
+    pub name: String,
     pub pmove: Cell,
-    pub computer: bool
+    pub computer: bool,
+    pub strategy: Strategy,
+    /// Play moves from the opening book while the game is still in it
+    pub use_book: bool,
+    /// Program run for the External strategy, e.g. "python3 bot.py"
+    pub bot_command: String,
+    /// Why the external bot could not be started, if it failed
+    pub bot_error: Option<String>,
+    /// Running bot and the command it was started with
+    bot: Option<(String, ExternalBot)>
 }
 
 impl Player {
     pub fn new(initial_move: Cell, is_computer: bool) -> Self {
         Self {
+            name: String::new(),
             pmove: initial_move,
-            computer: is_computer
+            computer: is_computer,
+            strategy: Strategy::Random,
+            use_book: false,
+            bot_command: String::new(),
+            bot_error: None,
+            bot: None
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Pick the computer's next move, or None if it should play randomly
+    ///
+    /// Searches block until they finish; use `start_search` to keep running meanwhile
+    pub fn choose_move(&mut self, game: &Game, solver: &Solver, book: &OpeningBook) -> Option<Move> {
+        if let Some(m) = self.book_move(game, book) {
+            return Some(m);
+        }
+        match self.strategy {
+            Strategy::Random => None,
+            Strategy::Perfect | Strategy::Engine => self.start_search(game, solver)?.wait(),
+            Strategy::External => self.external_bot()?.best_move(game)
+        }
+    }
+
+    /// Start working out the computer's next move without waiting for it
+    ///
+    /// Collect the move with `poll_move`
+    pub fn start_move(&mut self, game: &Game, solver: &Solver, book: &OpeningBook) -> Thinking {
+        if let Some(m) = self.book_move(game, book) {
+            return Thinking::Ready(Some(m));
+        }
+        match self.strategy {
+            Strategy::Random => Thinking::Ready(None),
+            Strategy::Perfect | Strategy::Engine => match self.start_search(game, solver) {
+                Some(search) => Thinking::Searching(search),
+                None => Thinking::Ready(None)
+            },
+            Strategy::External => self.ask_bot(game)
+        }
+    }
+
+    /// The move once it is ready, or None while it is still being worked out
+    ///
+    /// A ready move of None means the computer should play randomly
+    pub fn poll_move(&mut self, thinking: &mut Thinking, game: &Game) -> Option<Option<Move>> {
+        match thinking {
+            Thinking::Ready(m) => Some(m.clone()),
+            Thinking::Searching(search) => match search.poll() {
+                SearchStatus::Running => None,
+                SearchStatus::Done(m) => Some(m)
+            },
+            Thinking::LaunchingBot(command, launched) => match launched.try_recv() {
+                Err(TryRecvError::Empty) => None,
+                Ok(Ok(bot)) => {
+                    self.bot = Some((command.clone(), bot));
+                    *thinking = self.ask_bot(game);
+                    self.poll_move(thinking, game)
+                },
+                Ok(Err(e)) => {
+                    self.bot_error = Some(e.to_string());
+                    Some(None)
+                },
+                Err(TryRecvError::Disconnected) => Some(None)
+            },
+            Thinking::AskingBot => match &mut self.bot {
+                Some((_, bot)) => bot.poll_move(game),
+                None => Some(None)
+            }
+        }
+    }
+
+    /// The opening book's move, if this player uses the book and it has one
+    pub fn book_move(&self, game: &Game, book: &OpeningBook) -> Option<Move> {
+        if self.use_book { book.best_move(game) } else { None }
+    }
+
+    /// Start searching for a move on worker threads, or None if the strategy does not search
+    ///
+    /// Boards too large to solve are not searched, so perfect players fall back to random moves;
+    /// the engine searches boards of any size
+    pub fn start_search(&self, game: &Game, solver: &Solver) -> Option<Search> {
+        match self.strategy {
+            Strategy::Perfect if Solver::can_solve(game) => {
+                Some(Search::start(game, Searcher::Solver(solver.clone()), default_threads()))
+            },
+            Strategy::Engine => Some(Search::start(game, Searcher::Engine(ENGINE_DEPTH), default_threads())),
+            _ => None
         }
     }
-}
\ No newline at end of file
+
+    /// Send the position to the running bot, or start the bot on another thread if it is not
+    /// running yet, since a bot may take a while to say it is ready
+    fn ask_bot(&mut self, game: &Game) -> Thinking {
+        if self.bot.as_ref().is_some_and(|(command, _)| *command != self.bot_command) {
+            self.bot = None;
+        }
+        match &mut self.bot {
+            Some((_, bot)) => match bot.go(game) {
+                Ok(()) => Thinking::AskingBot,
+                Err(..) => Thinking::Ready(None)
+            },
+            None if self.bot_error.is_none() => {
+                let (sender, launched) = channel();
+                let command = self.bot_command.clone();
+                thread::spawn(move || { let _ = sender.send(ExternalBot::launch(&command)); });
+                Thinking::LaunchingBot(self.bot_command.clone(), launched)
+            },
+            None => Thinking::Ready(None)
+        }
+    }
+
+    /// The bot for the current command, started the first time it is needed
+    fn external_bot(&mut self) -> Option<&mut ExternalBot> {
+        if self.bot.as_ref().is_some_and(|(command, _)| *command != self.bot_command) {
+            self.bot = None;
+        }
+        if self.bot.is_none() && self.bot_error.is_none() {
+            match ExternalBot::launch(&self.bot_command) {
+                Ok(bot) => self.bot = Some((self.bot_command.clone(), bot)),
+                Err(e) => self.bot_error = Some(e.to_string())
+            }
+        }
+        self.bot.as_mut().map(|(_, bot)| bot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use crate::game_enums::{Mode, State};
+
+    #[cfg(unix)]
+    #[test]
+    fn bots_are_started_without_waiting() {
+        let script = "while read cmd rest; do case $cmd in \
+                      sos) sleep 1; echo sosok;; \
+                      isready) echo readyok;; \
+                      go) echo 'bestmove S 0 1';; \
+                      quit) exit;; esac; done";
+        let path = std::env::temp_dir().join(format!("sos_slow_start_{}.sh", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        let mut player = Player::new(Cell::S, true);
+        player.strategy = Strategy::External;
+        player.bot_command = format!("sh {}", path.display());
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+
+        let started = Instant::now();
+        let mut thinking = player.start_move(&g, &Solver::new(), &OpeningBook::default());
+        assert!(player.poll_move(&mut thinking, &g).is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let answer = loop {
+            if let Some(answer) = player.poll_move(&mut thinking, &g) {
+                break answer;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(answer, Some(Move { cell: Cell::S, row: 0, col: 1 }));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn engine_players_search_boards_too_large_to_solve() {
+        let mut player = Player::new(Cell::S, true);
+        player.strategy = Strategy::Engine;
+        let mut g = Game::new(Mode::Classic, 6);
+        g.state = State::Playing;
+        g.make_move(Cell::S, 5, 0);
+        g.make_move(Cell::S, 5, 2);
+
+        let mut thinking = player.start_move(&g, &Solver::new(), &OpeningBook::default());
+        assert!(thinking.progress().is_some());
+        let answer = loop {
+            if let Some(answer) = player.poll_move(&mut thinking, &g) {
+                break answer;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(answer, Some(Move { cell: Cell::O, row: 5, col: 1 }));
+    }
+}
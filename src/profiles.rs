@@ -0,0 +1,192 @@
+//!
+//! Named player profiles with lifetime statistics, kept in a local file
+//!
+//! Each line of the file is `<games>,<wins>,<draws>,<losses>,<sos>,<margin>,<name>`,
+//! where `sos` is every SOS the player completed and `margin` the sum of their score differences.
+//!
+
+use std::fs;
+use crate::game::Game;
+use crate::game_enums::Turn;
+use crate::recording::Recording;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub sos: u32,
+    pub margin: i64
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Default::default() }
+    }
+
+    pub fn sos_per_game(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.sos as f64 / self.games as f64 }
+    }
+
+    /// Average of the player's score minus their opponent's
+    pub fn average_margin(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.margin as f64 / self.games as f64 }
+    }
+
+    /// Short summary of the statistics
+    pub fn as_string(&self) -> String {
+        format!("{} games, {} wins, {} draws, {} losses\n{:.1} SOS per game, average margin {:+.1}",
+                self.games, self.wins, self.draws, self.losses, self.sos_per_game(), self.average_margin())
+    }
+}
+
+/// Every profile, in the order they were created
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// The profile called `name`, created if it does not exist yet
+    pub fn get_or_add(&mut self, name: &str) -> &mut Profile {
+        match self.profiles.iter().position(|p| p.name == name) {
+            Some(index) => &mut self.profiles[index],
+            None => {
+                self.profiles.push(Profile::new(name));
+                self.profiles.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Add a finished game between `left` and `right` to both profiles
+    ///
+    /// Games that have not finished are ignored
+    pub fn record(&mut self, left: &str, right: &str, game: &Game) {
+        if !game.state.is_over() {
+            return;
+        }
+        for (name, side) in [(left, Turn::Left), (right, Turn::Right)] {
+            let (scored, conceded) = match side {
+                Turn::Left => (game.left_score, game.right_score),
+                Turn::Right => (game.right_score, game.left_score)
+            };
+            let profile = self.get_or_add(name);
+            profile.games += 1;
+            profile.sos += scored;
+            profile.margin += scored as i64 - conceded as i64;
+            match game.state.winner() {
+                None => profile.draws += 1,
+                Some(winner) if winner == side => profile.wins += 1,
+                Some(_) => profile.losses += 1
+            }
+        }
+    }
+
+    /// Add the result of a recorded game to both profiles
+    pub fn record_recording(&mut self, left: &str, right: &str, recording: &Recording) {
+        self.record(left, right, &Game::from_recording(recording));
+    }
+
+    pub fn as_string(&self) -> String {
+        self.profiles.iter()
+            .map(|p| format!("{},{},{},{},{},{},{}\n", p.games, p.wins, p.draws, p.losses, p.sos, p.margin, p.name))
+            .collect()
+    }
+
+    pub fn write_to_file(&self, file_name: &str) -> std::io::Result<()> {
+        fs::write(file_name, self.as_string())
+    }
+
+    /// Read a profiles file, skipping lines that cannot be understood
+    pub fn read_from_file(file_name: &str) -> Option<Self> {
+        let mut profiles = Self::new();
+        for line in fs::read_to_string(file_name).ok()?.lines() {
+            let fields: Vec<&str> = line.splitn(7, ',').collect();
+            if let [games, wins, draws, losses, sos, margin, name] = fields.as_slice() {
+                let profile = (|| Some(Profile {
+                    name: name.to_string(),
+                    games: games.parse().ok()?,
+                    wins: wins.parse().ok()?,
+                    draws: draws.parse().ok()?,
+                    losses: losses.parse().ok()?,
+                    sos: sos.parse().ok()?,
+                    margin: margin.parse().ok()?
+                }))();
+                profiles.profiles.extend(profile);
+            }
+        }
+        Some(profiles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Cell, Mode, State};
+
+    fn finished_game() -> Game {
+        // Player 1 completes one SOS, then the board is filled without another
+        let mut g = Game::new(Mode::Classic, 3);
+        g.state = State::Playing;
+        for (cell, row, col) in [(Cell::S, 0, 0), (Cell::O, 0, 1), (Cell::S, 0, 2), (Cell::O, 1, 0),
+                                 (Cell::O, 1, 1), (Cell::O, 1, 2), (Cell::O, 2, 0), (Cell::O, 2, 1),
+                                 (Cell::O, 2, 2)] {
+            g.make_move(cell, row, col);
+        }
+        g
+    }
+
+    #[test]
+    fn finished_game_updates_both_profiles() {
+        let g = finished_game();
+        assert_eq!(g.state, State::LeftWin);
+        let mut profiles = Profiles::new();
+        profiles.record("Ann", "Bob", &g);
+
+        let ann = profiles.get("Ann").unwrap();
+        assert_eq!((ann.games, ann.wins, ann.sos, ann.margin), (1, 1, 1, 1));
+        let bob = profiles.get("Bob").unwrap();
+        assert_eq!((bob.games, bob.losses, bob.sos, bob.margin), (1, 1, 0, -1));
+        assert_eq!(bob.average_margin(), -1.0);
+    }
+
+    #[test]
+    fn unfinished_games_are_ignored() {
+        let mut g = Game::new(Mode::Simple, 3);
+        g.state = State::Playing;
+        let mut profiles = Profiles::new();
+        profiles.record("Ann", "Bob", &g);
+        assert!(profiles.profiles.is_empty());
+    }
+
+    #[test]
+    fn recordings_count_like_games() {
+        let g = finished_game();
+        let mut from_game = Profiles::new();
+        from_game.record("Ann", "Bob", &g);
+        let mut from_recording = Profiles::new();
+        from_recording.record_recording("Ann", "Bob", &g.recording);
+        assert_eq!(from_game, from_recording);
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut profiles = Profiles::new();
+        profiles.record("Ann, the first", "Bob", &finished_game());
+        let path = std::env::temp_dir().join(format!("sos_profiles_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        profiles.write_to_file(path).unwrap();
+        assert_eq!(Profiles::read_from_file(path), Some(profiles));
+        std::fs::remove_file(path).unwrap();
+    }
+}
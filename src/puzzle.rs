@@ -0,0 +1,209 @@
+//!
+//! "Find the SOS chain" puzzles
+//!
+//! Completing an SOS earns another turn, so a single turn can string several SOSs together.
+//! A puzzle is a position with the best known turn for the player to move. An answer counts if
+//! the engine rates the position it leaves at least as highly as the one the known turn leaves,
+//! so other orders of the same chain, or equally good chains, are also right.
+//!
+//! Puzzle files have one puzzle a line: a title, the position and the known turn, separated by `|`.
+//! The position is written as by `Game::to_notation` and the moves as by `Move::to_notation`.
+//!
+//! ```text
+//! # Comments and blank lines are skipped
+//! Four corners | S-S/---/S-S L 0 0 C | O b3, O a2, O c2, O b1, O b2
+//! ```
+//!
+
+use std::fs;
+use crate::engine::{evaluate, Engine};
+use crate::game::Game;
+use crate::game_enums::{Cell, State};
+use crate::recording::Move;
+
+/// How many letters ahead answers are judged
+pub const PUZZLE_DEPTH: u32 = 3;
+
+/// Puzzles that come with the game
+const BUILT_IN: &str = include_str!("../puzzles.txt");
+
+/// How an attempt at a puzzle stands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The player is still on turn
+    Going,
+    /// The turn ended at least as well as the known solution
+    Solved,
+    /// The turn ended worse than the known solution
+    Missed
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub title: String,
+    /// Position to solve, with the solver on turn
+    pub game: Game,
+    /// Best known turn: moves that each complete an SOS, then one that passes the turn or ends the game
+    pub solution: Vec<Move>
+}
+
+impl Puzzle {
+    /// Read a line of a puzzle file
+    ///
+    /// Returns None if the line is not a puzzle, or its solution is not exactly one legal turn
+    pub fn from_line(line: &str) -> Option<Self> {
+        let [title, position, moves] = line.split('|').map(str::trim).collect::<Vec<&str>>()[..] else {
+            return None;
+        };
+        let game = Game::from_notation(position)?;
+        let size = game.get_board_size();
+        let solution = moves.split(',')
+            .map(|m| Move::from_notation(m, size).filter(|m| m.cell != Cell::Empty))
+            .collect::<Option<Vec<Move>>>()?;
+
+        let mut end = game.clone();
+        for m in &solution {
+            if !turn_goes_on(&game, &end) || end.get_cell(m.col, m.row) != Ok(&Cell::Empty) {
+                return None;
+            }
+            end.make_move(m.cell, m.row, m.col);
+        }
+        if turn_goes_on(&game, &end) {
+            return None;
+        }
+        Some(Self { title: title.to_string(), game, solution })
+    }
+
+    pub fn as_line(&self) -> String {
+        let size = self.game.get_board_size();
+        let moves: Vec<String> = self.solution.iter().map(|m| m.to_notation(size)).collect();
+        format!("{} | {} | {}", self.title, self.game.to_notation(), moves.join(", "))
+    }
+
+    /// Read a puzzle file, or None if it cannot be read or any line is not a puzzle
+    pub fn read_from_file(file_name: &str) -> Option<Vec<Self>> {
+        parse_puzzles(&fs::read_to_string(file_name).ok()?)
+    }
+
+    /// Puzzles that come with the game
+    pub fn built_in() -> Vec<Self> {
+        parse_puzzles(BUILT_IN).expect("built-in puzzles are valid")
+    }
+
+    /// The puzzle's position after the known solution
+    pub fn solved(&self) -> Game {
+        let mut game = self.game.clone();
+        for m in &self.solution {
+            game.make_move(m.cell, m.row, m.col);
+        }
+        game
+    }
+
+    /// Number of SOSs the known solution completes
+    pub fn chain_length(&self) -> u32 {
+        let solved = self.solved();
+        solved.left_score + solved.right_score - self.game.left_score - self.game.right_score
+    }
+
+    /// Judge an attempt, `game` being the puzzle's position with the player's moves made
+    pub fn check(&self, game: &Game, engine: &Engine) -> Verdict {
+        self.judge(game, self.solution_value(engine), engine)
+    }
+
+    /// Value of the known solution for the solver, by the engine
+    ///
+    /// Worked out once per puzzle and passed to `judge`, rather than by every `check`
+    pub fn solution_value(&self, engine: &Engine) -> i32 {
+        turn_value(&self.game, &self.solved(), engine)
+    }
+
+    /// Judge an attempt against the known solution's value from `solution_value`
+    pub fn judge(&self, game: &Game, solution_value: i32, engine: &Engine) -> Verdict {
+        if turn_goes_on(&self.game, game) {
+            Verdict::Going
+        } else if turn_value(&self.game, game, engine) >= solution_value {
+            Verdict::Solved
+        } else {
+            Verdict::Missed
+        }
+    }
+}
+
+/// Puzzles in the text of a puzzle file
+fn parse_puzzles(text: &str) -> Option<Vec<Puzzle>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Puzzle::from_line)
+        .collect()
+}
+
+/// Whether the player on turn at `start` is still on turn at `now`, with the game going on
+fn turn_goes_on(start: &Game, now: &Game) -> bool {
+    now.state == State::Playing && now.turn == start.turn
+}
+
+/// Value of `end` for the player on turn at `start`, by the engine
+fn turn_value(start: &Game, end: &Game, engine: &Engine) -> i32 {
+    let value = engine.search(end).map_or_else(|| evaluate(end), |(_, value)| value);
+    if end.turn == start.turn { value } else { -value }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn four_corners() -> Puzzle {
+        Puzzle::from_line("Four corners | S-S/---/S-S L 0 0 C | O b3, O a2, O c2, O b1, O b2").unwrap()
+    }
+
+    #[test]
+    fn reads_and_writes_puzzle_lines() {
+        let puzzle = four_corners();
+        assert_eq!(puzzle.title, "Four corners");
+        assert_eq!(puzzle.solution[0], Move { cell: Cell::O, row: 0, col: 1 });
+        assert_eq!(puzzle.chain_length(), 6);
+        assert_eq!(Puzzle::from_line(&puzzle.as_line()).unwrap().as_line(), puzzle.as_line());
+    }
+
+    #[test]
+    fn solutions_must_be_one_whole_turn() {
+        // Stops while still on turn
+        assert!(Puzzle::from_line("Short | S-S/---/S-S L 0 0 C | O b3").is_none());
+        // Carries on after the turn has passed
+        assert!(Puzzle::from_line("Long | S-S/---/S-S L 0 0 C | S b3, O a2").is_none());
+        assert!(Puzzle::from_line("Taken | S-S/---/S-S L 0 0 C | S a3").is_none());
+        assert!(Puzzle::from_line("S-S/---/S-S L 0 0 C | O b3").is_none());
+    }
+
+    #[test]
+    fn answers_are_judged_by_where_the_turn_ends() {
+        let puzzle = four_corners();
+        let engine = Engine::new(PUZZLE_DEPTH);
+        let mut attempt = puzzle.game.clone();
+        attempt.make_move(Cell::O, 1, 0);
+        assert_eq!(puzzle.check(&attempt, &engine), Verdict::Going);
+
+        // The same chain in another order
+        let mut other_order = attempt.clone();
+        for (row, col) in [(2, 1), (0, 1), (1, 2), (1, 1)] {
+            other_order.make_move(Cell::O, row, col);
+        }
+        assert_eq!(puzzle.check(&other_order, &engine), Verdict::Solved);
+
+        // Breaking off the chain with an S hands the rest to the opponent
+        attempt.make_move(Cell::S, 1, 1);
+        assert_eq!(puzzle.check(&attempt, &engine), Verdict::Missed);
+    }
+
+    #[test]
+    fn built_in_puzzles_are_solved_by_their_solutions() {
+        let engine = Engine::new(PUZZLE_DEPTH);
+        let puzzles = Puzzle::built_in();
+        assert!(!puzzles.is_empty());
+        for puzzle in puzzles {
+            assert!(puzzle.chain_length() > 0, "{}", puzzle.title);
+            assert_eq!(puzzle.check(&puzzle.solved(), &engine), Verdict::Solved, "{}", puzzle.title);
+        }
+    }
+}
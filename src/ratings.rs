@@ -0,0 +1,176 @@
+//!
+//! Elo ratings kept in a local file, separately for each mode
+//!
+//! Each line of the file is `<C|S>,<rating>,<games>,<name>`.
+//!
+
+use std::collections::HashMap;
+use std::fs;
+use crate::game_enums::{Mode, State, Turn};
+
+/// Rating given to a player before their first rated game
+pub const DEFAULT_RATING: f64 = 1500.0;
+/// Largest change to a rating from a single game
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub games: u32
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self { rating: DEFAULT_RATING, games: 0 }
+    }
+}
+
+/// Every player's rating, by name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ratings {
+    classic: HashMap<String, Rating>,
+    simple: HashMap<String, Rating>
+}
+
+impl Ratings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rating of `name` in `mode`, the default for unknown players
+    pub fn get(&self, name: &str, mode: &Mode) -> Rating {
+        self.table(mode).get(name).copied().unwrap_or_default()
+    }
+
+    /// Update both players after a finished game between `left` and `right`
+    ///
+    /// Games that have not finished, and games a player played against themselves, are ignored
+    pub fn record(&mut self, left: &str, right: &str, mode: &Mode, state: State) {
+        if !state.is_over() || left == right {
+            return;
+        }
+        let left_result = match state.winner() {
+            Some(Turn::Left) => 1.0,
+            Some(Turn::Right) => 0.0,
+            None => 0.5
+        };
+        let old_left = self.get(left, mode);
+        let old_right = self.get(right, mode);
+        let change = K_FACTOR * (left_result - expected_score(old_left.rating, old_right.rating));
+
+        let table = self.table_mut(mode);
+        table.insert(left.to_string(), Rating { rating: old_left.rating + change, games: old_left.games + 1 });
+        table.insert(right.to_string(), Rating { rating: old_right.rating - change, games: old_right.games + 1 });
+    }
+
+    pub fn as_string(&self) -> String {
+        let mut lines = Vec::new();
+        for (mode, table) in [("C", &self.classic), ("S", &self.simple)] {
+            for (name, rating) in table {
+                lines.push(format!("{},{},{},{}", mode, rating.rating, rating.games, name));
+            }
+        }
+        lines.sort();
+        lines.iter().map(|line| line.to_string() + "\n").collect()
+    }
+
+    pub fn write_to_file(&self, file_name: &str) -> std::io::Result<()> {
+        fs::write(file_name, self.as_string())
+    }
+
+    /// Read a ratings file, skipping lines that cannot be understood
+    pub fn read_from_file(file_name: &str) -> Option<Self> {
+        let mut ratings = Self::new();
+        for line in fs::read_to_string(file_name).ok()?.lines() {
+            let fields: Vec<&str> = line.splitn(4, ',').collect();
+            if let [mode, rating, games, name] = fields.as_slice() {
+                let mode = match *mode {
+                    "C" => Mode::Classic,
+                    "S" => Mode::Simple,
+                    _ => continue
+                };
+                if let (Ok(rating), Ok(games)) = (rating.parse(), games.parse()) {
+                    ratings.table_mut(&mode).insert(name.to_string(), Rating { rating, games });
+                }
+            }
+        }
+        Some(ratings)
+    }
+
+    fn table(&self, mode: &Mode) -> &HashMap<String, Rating> {
+        match mode {
+            Mode::Classic => &self.classic,
+            Mode::Simple => &self.simple
+        }
+    }
+
+    fn table_mut(&mut self, mode: &Mode) -> &mut HashMap<String, Rating> {
+        match mode {
+            Mode::Classic => &mut self.classic,
+            Mode::Simple => &mut self.simple
+        }
+    }
+}
+
+/// Chance of a player rated `rating` beating one rated `opponent`, counting draws as half
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_players_start_at_default() {
+        let ratings = Ratings::new();
+        assert_eq!(ratings.get("Ann", &Mode::Classic), Rating { rating: DEFAULT_RATING, games: 0 });
+    }
+
+    #[test]
+    fn winner_gains_what_loser_loses() {
+        let mut ratings = Ratings::new();
+        ratings.record("Ann", "Bob", &Mode::Classic, State::LeftWin);
+        let ann = ratings.get("Ann", &Mode::Classic);
+        let bob = ratings.get("Bob", &Mode::Classic);
+        assert_eq!(ann.rating, DEFAULT_RATING + K_FACTOR / 2.0);
+        assert_eq!(bob.rating, DEFAULT_RATING - K_FACTOR / 2.0);
+        assert_eq!(ann.games, 1);
+    }
+
+    #[test]
+    fn modes_are_rated_separately() {
+        let mut ratings = Ratings::new();
+        ratings.record("Ann", "Bob", &Mode::Simple, State::RightWin);
+        ratings.record("Ann", "Bob", &Mode::Simple, State::Playing);
+        assert_eq!(ratings.get("Ann", &Mode::Classic).games, 0);
+        assert_eq!(ratings.get("Ann", &Mode::Simple).games, 1);
+        assert!(ratings.get("Bob", &Mode::Simple).rating > DEFAULT_RATING);
+    }
+
+    #[test]
+    fn draw_between_equals_changes_nothing() {
+        let mut ratings = Ratings::new();
+        ratings.record("Ann", "Bob", &Mode::Classic, State::Draw);
+        assert_eq!(ratings.get("Ann", &Mode::Classic).rating, DEFAULT_RATING);
+    }
+
+    #[test]
+    fn games_against_yourself_are_not_rated() {
+        let mut ratings = Ratings::new();
+        ratings.record("Ann", "Ann", &Mode::Classic, State::LeftWin);
+        assert_eq!(ratings.get("Ann", &Mode::Classic), Rating { rating: DEFAULT_RATING, games: 0 });
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut ratings = Ratings::new();
+        ratings.record("Ann, the first", "Bob", &Mode::Classic, State::LeftWin);
+        ratings.record("Bob", "Cy", &Mode::Simple, State::Draw);
+        let path = std::env::temp_dir().join(format!("sos_ratings_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        ratings.write_to_file(path).unwrap();
+        assert_eq!(Ratings::read_from_file(path), Some(ratings));
+        std::fs::remove_file(path).unwrap();
+    }
+}
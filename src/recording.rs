@@ -1,6 +1,9 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
-use crate::game_enums::{Cell, Mode};
+use std::time::Duration;
+use crate::bitboard::{Bitboard, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::game_enums::{Cell, Mode, State, TimeControl, Turn};
+use crate::notation::{board_string, parse_board, parse_cell, parse_state, state_string};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Move {
@@ -9,11 +12,63 @@ pub struct Move {
     pub col: usize
 }
 
-#[derive(Debug, PartialEq)]
+impl Move {
+    /// The move on a `board_size` board as its letter and square, e.g. `S c4`, or `pass` for a
+    /// turn given up
+    ///
+    /// Columns are lettered from `a` on the left and rows numbered from 1 at the bottom. The
+    /// square must be on the board.
+    pub fn to_notation(&self, board_size: usize) -> String {
+        let letter = match self.cell {
+            Cell::S => "S",
+            Cell::O => "O",
+            Cell::Empty => return "pass".to_string()
+        };
+        assert!(self.row < board_size && self.col < board_size, "move is off the board");
+        format!("{} {}{}", letter, (b'a' + self.col as u8) as char, board_size - self.row)
+    }
+
+    /// Read a move written by `to_notation`, if its square is on a `board_size` board
+    pub fn from_notation(s: &str, board_size: usize) -> Option<Self> {
+        let (letter, square) = match s.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["pass"] => return Some(Move { cell: Cell::Empty, row: 0, col: 0 }),
+            [letter, square] => (*letter, *square),
+            _ => return None
+        };
+        let cell = parse_cell(&letter.to_uppercase())?;
+        let mut chars = square.chars();
+        let col = (chars.next()?.to_ascii_lowercase() as usize).checked_sub('a' as usize)?;
+        let rank = chars.as_str().parse::<usize>().ok()?;
+        if col >= board_size || rank == 0 || rank > board_size {
+            return None;
+        }
+        Some(Move { cell, row: board_size - rank, col })
+    }
+}
+
+/// Position a game was set up in before its first move
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setup {
+    pub board: Bitboard,
+    pub left_score: u32,
+    pub right_score: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Recording {
     pub mode: Mode,
     pub board_size: usize,
+    /// Player who made the first move
+    pub first_turn: Turn,
+    pub time_control: TimeControl,
+    /// Starting position, for games that did not start from an empty board
+    pub setup: Option<Setup>,
+    /// Moves in order; a move of `Cell::Empty` is a turn given up on time
     pub moves: Vec<Move>,
+    /// Time taken for each move, in timed games
+    pub move_times: Vec<Duration>,
+    /// How the game ended, if it ended before the board decided it
+    pub ending: Option<State>,
     current_move: usize,
 }
 
@@ -22,7 +77,12 @@ impl Recording {
         Self {
             mode,
             board_size,
+            first_turn: Turn::Left,
+            time_control: TimeControl::Untimed,
+            setup: None,
             moves: Vec::new(),
+            move_times: Vec::new(),
+            ending: None,
             current_move: 0
         }
     }
@@ -43,18 +103,37 @@ impl Recording {
         let mut string = match self.mode {
             Mode::Classic => "C",
             Mode::Simple => "S"
-        }.to_string() + "," + &*self.board_size.to_string();
+        }.to_string() + "," + &*self.board_size.to_string() + match self.first_turn {
+            Turn::Left => ",L",
+            Turn::Right => ",R"
+        };
+        match self.time_control {
+            TimeControl::Untimed => (),
+            TimeControl::PerMove(limit) => string += &format!(",M{}", limit.as_millis()),
+            TimeControl::Total(total, increment) =>
+                string += &format!(",T{}+{}", total.as_millis(), increment.as_millis())
+        }
+        if let Some(setup) = &self.setup {
+            string += &format!("\nB,{},{},{}", board_string(&setup.board), setup.left_score, setup.right_score);
+        }
 
-        for m in self.moves.clone() {
+        for (i, m) in self.moves.iter().enumerate() {
             string += "\n";
             string += match m.cell {
                 Cell::S => "S",
                 Cell::O => "O",
-                Cell::Empty => ""
+                Cell::Empty => "P"
             };
             string += &*(",".to_owned() + &*m.row.to_string() + "," + &*m.col.to_string());
+            if let Some(time) = self.move_times.get(i) {
+                string += &format!(",{}", time.as_millis());
+            }
+        }
+        if let Some(ending) = self.ending {
+            string += "\nE,";
+            string += state_string(ending);
         }
-        return string;
+        string
     }
     pub fn write_to_file(&self, file_name: String) {
         let mut f = BufWriter::new(File::create(file_name).unwrap());
@@ -62,17 +141,10 @@ impl Recording {
     }
     pub fn read_from_file(file_name: String) -> Option<Self> {
         let mut first_line = String::new();
-        let f = File::open(file_name);
-        match f {
-            Err(..) => return None,
-            _ => ()
-        }
+        let f = File::open(file_name).ok()?;
 
-        let mut br = BufReader::new(f.unwrap());
-        match br.read_line(&mut first_line) {
-            Err(..) => return None,
-            _ => ()
-        };
+        let mut br = BufReader::new(f);
+        br.read_line(&mut first_line).ok()?;
 
         // read_line keeps trailing \r\n
         // we need to remove that to parse the usize
@@ -83,13 +155,10 @@ impl Recording {
             }
         }
 
-        let first_line_vec:Vec<&str> = first_line.split(",").collect();
+        let first_line_vec:Vec<&str> = first_line.split(',').collect();
 
-        let board_size = first_line_vec[1].parse::<usize>();
-        match board_size {
-            Err(..) => return None,
-            _ => ()
-        };
+        let board_size = first_line_vec.get(1)?.parse::<usize>().ok()
+            .filter(|size| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(size))?;
 
         let mut new_record = Self::new(
             match first_line_vec[0] {
@@ -97,45 +166,64 @@ impl Recording {
                 "S" => Mode::Simple,
                 _ => Mode::Classic
             },
-            board_size.unwrap(),
+            board_size,
         );
+        // Recordings from before the first player was saved always started with Player 1
+        if first_line_vec.get(2) == Some(&"R") {
+            new_record.first_turn = Turn::Right;
+        }
+        if let Some(time_control) = first_line_vec.get(3) {
+            new_record.time_control = parse_time_control(time_control)?;
+        }
 
         for line in br.lines() {
-            let line_str = line;
-            match line_str {
-                Err(..) => return None,
-                _ => ()
-            };
-            // This avoids a "temporary value dropped while borrowed" error
-            let line_str = line_str.unwrap();
-
-            let line_vec:Vec<&str> = line_str.split(",").collect();
-            let row = line_vec[1].parse::<usize>();
-            match row {
-                Err(..) => return None,
-                _ => ()
-            };
+            let line_str = line.ok()?;
 
-            let col = line_vec[2].parse::<usize>();
-            match col {
-                Err(..) => return None,
-                _ => ()
-            };
+            let line_vec:Vec<&str> = line_str.split(',').collect();
+            if line_vec[0] == "E" {
+                new_record.ending = Some(parse_state(line_vec.get(1)?)?);
+                continue;
+            }
+            if line_vec[0] == "B" {
+                let board = parse_board(line_vec.get(1)?).filter(|board| board.size() == board_size)?;
+                new_record.setup = Some(Setup {
+                    board,
+                    left_score: line_vec.get(2)?.parse().ok()?,
+                    right_score: line_vec.get(3)?.parse().ok()?
+                });
+                continue;
+            }
+            let row = line_vec.get(1)?.parse::<usize>().ok().filter(|row| *row < board_size)?;
+            let col = line_vec.get(2)?.parse::<usize>().ok().filter(|col| *col < board_size)?;
 
             new_record.add_move(
                 match line_vec[0] {
                     "S" => Cell::S,
                     "O" => Cell::O,
-                    _ => Cell::Empty
+                    "P" => Cell::Empty,
+                    _ => return None
                 },
-                row.unwrap(),
-                col.unwrap()
+                row,
+                col
             );
+            if let Some(time) = line_vec.get(3) {
+                new_record.move_times.push(Duration::from_millis(time.parse().ok()?));
+            }
         }
-        return Some(new_record);
+        Some(new_record)
     }
 }
 
+/// Read a time control written in a recording header, `M<ms>` or `T<ms>+<ms>`
+fn parse_time_control(s: &str) -> Option<TimeControl> {
+    if let Some(limit) = s.strip_prefix('M') {
+        return Some(TimeControl::PerMove(Duration::from_millis(limit.parse().ok()?)));
+    }
+    let (total, increment) = s.strip_prefix('T')?.split_once('+')?;
+    Some(TimeControl::Total(Duration::from_millis(total.parse().ok()?),
+                            Duration::from_millis(increment.parse().ok()?)))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -181,6 +269,94 @@ mod test {
         assert_eq!(m, None);
     }
 
+    #[test]
+    fn first_turn_survives_a_round_trip() {
+        let mut recording = Recording::new(Mode::Classic, 4);
+        recording.first_turn = Turn::Right;
+        recording.add_move(Cell::O, 2, 3);
+        assert_eq!(recording.as_string(), "C,4,R\nO,2,3");
+
+        let path = std::env::temp_dir().join(format!("sos_recording_{}.sos", std::process::id()));
+        recording.write_to_file(path.to_str().unwrap().to_string());
+        let read = Recording::read_from_file(path.to_str().unwrap().to_string()).unwrap();
+        assert_eq!(read.first_turn, Turn::Right);
+        assert_eq!(read.moves, recording.moves);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn clock_times_passes_and_ending_survive_a_round_trip() {
+        let mut recording = Recording::new(Mode::Simple, 3);
+        recording.time_control = TimeControl::Total(Duration::from_secs(60), Duration::from_secs(2));
+        recording.add_move(Cell::S, 0, 0);
+        recording.add_move(Cell::Empty, 0, 0);
+        recording.move_times = vec![Duration::from_millis(1500), Duration::from_millis(61000)];
+        recording.ending = Some(State::TimeOut(Turn::Left));
+        assert_eq!(recording.as_string(), "S,3,L,T60000+2000\nS,0,0,1500\nP,0,0,61000\nE,LEFTTIMEOUT");
+
+        let path = std::env::temp_dir().join(format!("sos_timed_{}.sos", std::process::id()));
+        recording.write_to_file(path.to_str().unwrap().to_string());
+        let read = Recording::read_from_file(path.to_str().unwrap().to_string()).unwrap();
+        assert_eq!(read, recording);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn setup_survives_a_round_trip() {
+        let mut board = Bitboard::new(3);
+        board.set(0, 0, Cell::S);
+        board.set(1, 2, Cell::O);
+        let mut recording = Recording::new(Mode::Classic, 3);
+        recording.setup = Some(Setup { board, left_score: 2, right_score: 1 });
+        recording.add_move(Cell::S, 2, 2);
+        assert_eq!(recording.as_string(), "C,3,L\nB,S--/--O/---,2,1\nS,2,2");
+
+        let path = std::env::temp_dir().join(format!("sos_setup_{}.sos", std::process::id()));
+        recording.write_to_file(path.to_str().unwrap().to_string());
+        let read = Recording::read_from_file(path.to_str().unwrap().to_string()).unwrap();
+        assert_eq!(read, recording);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn moves_read_back_from_notation() {
+        let m = Move { cell: Cell::S, row: 1, col: 2 };
+        assert_eq!(m.to_notation(5), "S c4");
+        assert_eq!(Move::from_notation("S c4", 5), Some(m));
+        assert_eq!(Move::from_notation("o a1", 3), Some(Move { cell: Cell::O, row: 2, col: 0 }));
+        let pass = Move { cell: Cell::Empty, row: 0, col: 0 };
+        assert_eq!(Move::from_notation(&pass.to_notation(3), 3), Some(pass));
+        for text in ["S d1", "S a4", "S a0", "X a1", "S", "S 1a"] {
+            assert_eq!(Move::from_notation(text, 3), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn unknown_letters_are_not_read_as_passes() {
+        let path = std::env::temp_dir().join(format!("sos_corrupt_{}.sos", std::process::id()));
+        std::fs::write(&path, "C,3,L\nS,0,0\nX,1,1").unwrap();
+        assert_eq!(Recording::read_from_file(path.to_str().unwrap().to_string()), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn boards_too_large_to_play_are_not_read() {
+        let path = std::env::temp_dir().join(format!("sos_oversize_{}.sos", std::process::id()));
+        std::fs::write(&path, format!("C,{},L\nS,0,0", MAX_BOARD_SIZE + 1)).unwrap();
+        assert_eq!(Recording::read_from_file(path.to_str().unwrap().to_string()), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn moves_off_the_board_are_not_read() {
+        let path = std::env::temp_dir().join(format!("sos_off_board_{}.sos", std::process::id()));
+        for moves in ["S,9,1", "O,1,5"] {
+            std::fs::write(&path, format!("C,5,L\n{}\nO,0,0", moves)).unwrap();
+            assert_eq!(Recording::read_from_file(path.to_str().unwrap().to_string()), None, "{}", moves);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn read_file_returns_none_if_not_found() {
         let recording = Recording::read_from_file(String::from("this_file_does_not_exist"));
@@ -0,0 +1,341 @@
+//!
+//! Move searches run on worker threads
+//!
+//! The moves at the root are shared out between the workers one at a time. Workers searching
+//! with the engine share the best value found so far, so later moves only need to be searched
+//! well enough to show they are no better.
+//!
+
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::engine::{self, Engine};
+use crate::game::Game;
+use crate::game_enums::{Mode, State, Turn};
+use crate::recording::Move;
+use crate::solver::Solver;
+use crate::transposition::TranspositionTable;
+
+/// What values the moves of a search
+#[derive(Clone)]
+pub enum Searcher {
+    /// Depth-limited search to this many letters
+    Engine(u32),
+    /// Perfect play, sharing the table of this solver; only for boards it can solve
+    Solver(Solver)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchStatus {
+    Running,
+    /// The best move, or None if there was no move to make
+    Done(Option<Move>)
+}
+
+/// Expected outcome of a position, from Player 1's point of view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Evaluation {
+    /// Player 1's final score minus Player 2's, in a Classic game
+    ScoreDifference(i32),
+    /// A won game for this player
+    Win(Turn),
+    /// A Simple game that neither player is winning
+    Even
+}
+
+impl Evaluation {
+    /// Outcome of a game that is over
+    pub fn of_finished(game: &Game) -> Self {
+        match (game.get_mode(), game.state.winner()) {
+            (Mode::Classic, _) if matches!(game.state, State::LeftWin | State::RightWin | State::Draw) => {
+                Evaluation::ScoreDifference(game.left_score as i32 - game.right_score as i32)
+            },
+            (_, Some(winner)) => Evaluation::Win(winner),
+            (_, None) => Evaluation::Even
+        }
+    }
+
+    /// Player 1's share of an evaluation bar, from 0 to 1
+    pub fn left_share(&self) -> f32 {
+        match self {
+            // Each extra SOS of lead fills less of the rest of the bar
+            Evaluation::ScoreDifference(difference) => 0.5 + 0.5 * *difference as f32 / (difference.abs() as f32 + 3.0),
+            Evaluation::Win(Turn::Left) => 1.0,
+            Evaluation::Win(Turn::Right) => 0.0,
+            Evaluation::Even => 0.5
+        }
+    }
+
+    pub fn as_string(&self) -> String {
+        match self {
+            Evaluation::ScoreDifference(difference) => format!("{:+}", difference),
+            Evaluation::Win(Turn::Left) => "P1 wins".to_string(),
+            Evaluation::Win(Turn::Right) => "P2 wins".to_string(),
+            Evaluation::Even => "Even".to_string()
+        }
+    }
+}
+
+/// A search running in the background, cancelled when dropped
+pub struct Search {
+    stop: Arc<AtomicBool>,
+    searched: Arc<AtomicUsize>,
+    total: usize,
+    result: Receiver<Option<(Move, i32)>>,
+    finished: Option<Option<(Move, i32)>>,
+    /// What the best value means, worked out when the search started
+    evaluation: Box<dyn Fn(i32) -> Evaluation + Send>
+}
+
+impl Search {
+    /// Start searching `game` on `threads` workers
+    pub fn start(game: &Game, searcher: Searcher, threads: usize) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let searched = Arc::new(AtomicUsize::new(0));
+        let moves = match game.state {
+            State::Playing => engine::root_moves(game),
+            _ => Vec::new()
+        };
+        let total = moves.len();
+        let (sender, result) = mpsc::channel();
+        let evaluation = evaluation_of(game, &searcher);
+
+        let game = game.clone();
+        let worker_stop = stop.clone();
+        let worker_searched = searched.clone();
+        thread::spawn(move || {
+            let best = search_moves(&game, &searcher, &moves, threads.max(1), &worker_stop, &worker_searched);
+            if !worker_stop.load(Ordering::Relaxed) {
+                let _ = sender.send(best);
+            }
+        });
+        Self { stop, searched, total, result, finished: None, evaluation }
+    }
+
+    /// Check for the result without waiting
+    pub fn poll(&mut self) -> SearchStatus {
+        if self.stop.load(Ordering::Relaxed) {
+            return SearchStatus::Done(None);
+        }
+        if self.finished.is_none() {
+            match self.result.try_recv() {
+                Ok(best) => self.finished = Some(best),
+                Err(TryRecvError::Disconnected) => self.finished = Some(None),
+                Err(TryRecvError::Empty) => ()
+            }
+        }
+        match &self.finished {
+            Some(best) => SearchStatus::Done(best.as_ref().map(|(m, _)| m.clone())),
+            None => SearchStatus::Running
+        }
+    }
+
+    /// Block until the search is done
+    pub fn wait(mut self) -> Option<Move> {
+        if self.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        match self.finished.take() {
+            Some(best) => best,
+            None => self.result.recv().ok().flatten()
+        }.map(|(m, _)| m)
+    }
+
+    /// Expected outcome with the best move, once `poll` has found the search done
+    pub fn evaluation(&self) -> Option<Evaluation> {
+        self.finished.as_ref()?.as_ref().map(|(_, value)| (self.evaluation)(*value))
+    }
+
+    /// Share of the root moves searched so far, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 { 1.0 } else { self.searched.load(Ordering::Relaxed) as f32 / self.total as f32 }
+    }
+
+    /// Stop the workers as soon as they notice; the search then ends without a move
+    pub fn cancel(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Number of workers to use, one per available core
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// One thread's share of a search
+enum Worker {
+    Engine(Engine, TranspositionTable),
+    Solver(Solver)
+}
+
+impl Worker {
+    /// Value of `m`, exact if it is at least `alpha`, the best value found so far
+    fn value(&mut self, game: &Game, m: &Move, alpha: i32) -> i32 {
+        match self {
+            // One below alpha, so a move as good as the best gets an exact value
+            Worker::Engine(engine, table) => engine.root_value(game, m, alpha - 1, table),
+            Worker::Solver(solver) => solver.move_value(game, m)
+        }
+    }
+}
+
+/// Turns the best value of a search of `game` into an evaluation
+fn evaluation_of(game: &Game, searcher: &Searcher) -> Box<dyn Fn(i32) -> Evaluation + Send> {
+    let turn = game.turn;
+    let simple = game.get_mode() == Mode::Simple;
+    // The engine's values count the scores so far, the solver's only what is still to come
+    let lead = match searcher {
+        Searcher::Engine(_) => 0,
+        Searcher::Solver(_) => game.left_score as i32 - game.right_score as i32
+    };
+    Box::new(move |value| {
+        let value = match turn {
+            Turn::Left => value,
+            Turn::Right => -value
+        };
+        match (simple, value.signum()) {
+            (false, _) => Evaluation::ScoreDifference(lead + value),
+            (true, 1) => Evaluation::Win(Turn::Left),
+            (true, -1) => Evaluation::Win(Turn::Right),
+            (true, _) => Evaluation::Even
+        }
+    })
+}
+
+/// The best of `moves` and its value, ties going to the earliest
+fn search_moves(game: &Game, searcher: &Searcher, moves: &[Move], threads: usize,
+                stop: &Arc<AtomicBool>, searched: &AtomicUsize) -> Option<(Move, i32)> {
+    let next = AtomicUsize::new(0);
+    let alpha = AtomicI32::new(-2 * engine::WIN);
+    let values = Mutex::new(vec![None; moves.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(moves.len()) {
+            scope.spawn(|| {
+                let mut worker = match searcher {
+                    Searcher::Engine(depth) => Worker::Engine(Engine::new(*depth).with_stop(stop.clone()),
+                                                              TranspositionTable::new(engine::TABLE_SIZE)),
+                    Searcher::Solver(solver) => Worker::Solver(solver.clone().with_stop(stop.clone()))
+                };
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= moves.len() || stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let value = worker.value(game, &moves[index], alpha.load(Ordering::Relaxed));
+                    alpha.fetch_max(value, Ordering::Relaxed);
+                    values.lock().unwrap()[index] = Some(value);
+                    searched.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    let values = values.into_inner().unwrap();
+    let mut best: Option<(usize, i32)> = None;
+    for (index, value) in values.iter().enumerate() {
+        if let Some(value) = value {
+            if best.is_none_or(|(_, best_value)| *value > best_value) {
+                best = Some((index, *value));
+            }
+        }
+    }
+    best.map(|(index, value)| (moves[index].clone(), value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Cell, Mode};
+
+    fn playing(mode: Mode, size: usize) -> Game {
+        let mut g = Game::new(mode, size);
+        g.state = State::Playing;
+        g
+    }
+
+    fn finish(search: &mut Search) {
+        while search.poll() == SearchStatus::Running {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn parallel_engine_search_matches_the_engine() {
+        let mut g = playing(Mode::Classic, 5);
+        for (cell, row, col) in [(Cell::S, 0, 0), (Cell::O, 1, 1), (Cell::S, 2, 4), (Cell::O, 3, 3)] {
+            g.make_move(cell, row, col);
+        }
+        let expected = Engine::new(3).best_move(&g);
+        for threads in [1, 4] {
+            assert_eq!(Search::start(&g, Searcher::Engine(3), threads).wait(), expected);
+        }
+    }
+
+    #[test]
+    fn parallel_solver_finds_the_winning_move() {
+        let mut g = playing(Mode::Simple, 3);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        let mut search = Search::start(&g, Searcher::Solver(Solver::new()), 3);
+        finish(&mut search);
+        assert_eq!(search.progress(), 1.0);
+        assert_eq!(search.poll(), SearchStatus::Done(Some(Move { cell: Cell::O, row: 0, col: 1 })));
+    }
+
+    #[test]
+    fn cancelled_search_stops_without_a_move() {
+        let g = playing(Mode::Classic, 3);
+        let mut solver = Solver::new();
+        let search = Search::start(&g, Searcher::Solver(solver.clone()), 2);
+        search.cancel();
+        assert_eq!(search.wait(), None);
+        // The shared table was left without half-finished values
+        assert_eq!(solver.value(&g), Solver::new().value(&g));
+    }
+
+    #[test]
+    fn finished_game_has_no_move() {
+        let g = Game::new(Mode::Classic, 4);
+        assert_eq!(Search::start(&g, Searcher::Engine(2), 2).wait(), None);
+    }
+
+    #[test]
+    fn evaluations_are_from_player_one_point_of_view() {
+        // Player 2 to move can complete an SOS
+        let mut g = playing(Mode::Classic, 3);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::O, 1, 1);
+        g.make_move(Cell::S, 2, 1);
+        let mut by_engine = Search::start(&g, Searcher::Engine(1), 2);
+        finish(&mut by_engine);
+        assert_eq!(by_engine.evaluation(), Some(Evaluation::ScoreDifference(-1)));
+
+        let mut simple = playing(Mode::Simple, 3);
+        simple.make_move(Cell::S, 0, 0);
+        simple.make_move(Cell::S, 0, 2);
+        let mut by_solver = Search::start(&simple, Searcher::Solver(Solver::new()), 2);
+        finish(&mut by_solver);
+        assert_eq!(by_solver.evaluation(), Some(Evaluation::Win(Turn::Left)));
+        assert_eq!(Evaluation::Win(Turn::Left).left_share(), 1.0);
+        assert!(Evaluation::ScoreDifference(-1).left_share() < 0.5);
+    }
+
+    #[test]
+    fn finished_games_are_evaluated_by_their_result() {
+        let mut g = playing(Mode::Classic, 3);
+        g.left_score = 2;
+        g.state = State::LeftWin;
+        assert_eq!(Evaluation::of_finished(&g), Evaluation::ScoreDifference(2));
+        g.state = State::Resigned(Turn::Left);
+        assert_eq!(Evaluation::of_finished(&g), Evaluation::Win(Turn::Right));
+        assert_eq!(Evaluation::ScoreDifference(2).as_string(), "+2");
+    }
+}
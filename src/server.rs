@@ -0,0 +1,522 @@
+//!
+//! Headless server hosting many games at once
+//!
+//! Clients send one command per line, starting with `HELLO`. Replies go to the client that asked,
+//! while moves and updates are pushed to every client seated in or watching the game. Tokens use
+//! the same spelling as the two-player network protocol.
+//!
+//! ```text
+//! HELLO <version>                 -> WELCOME <version>
+//! LIST                            -> GAMES <id> <id> ...
+//! CREATE <C|S> <size>             -> CREATED <id>
+//! JOIN <id>                       -> JOINED <id> <L|R>, then BOARD and UPDATE
+//! WATCH <id>                      -> WATCHING <id>, then a MOVE for each move so far, BOARD and UPDATE
+//! BOARD <id>                      -> BOARD <id> <size> <rows separated by />
+//! MOVE <id> <S|O> <row> <col>     -> MOVE <id> <S|O> <row> <col>, then UPDATE <id> <turn> <left> <right> <state>
+//! QUIT
+//! ```
+//!
+//! Anything that cannot be done is answered with `ERROR <reason>`. Games nobody joined go when
+//! their creator disconnects, and finished games stay until everyone in or watching them has gone.
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::bitboard::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::game::Game;
+use crate::game_enums::{State, Turn};
+use crate::network::PROTOCOL_VERSION;
+use crate::notation::{cell_string, parse_cell, parse_mode, rows_string, state_string, turn_string};
+
+pub const DEFAULT_SERVER_PORT: u16 = 7979;
+/// How many games a client may have created that nobody has joined yet
+pub const MAX_UNJOINED_GAMES: usize = 4;
+
+/// A client seated in a game
+struct Seat {
+    client: u32,
+    side: Turn,
+    outbox: Sender<String>
+}
+
+struct HostedGame {
+    game: Game,
+    /// Client that created the game
+    creator: u32,
+    seats: Vec<Seat>,
+    /// Clients following the game without playing
+    watchers: Vec<(u32, Sender<String>)>
+}
+
+impl HostedGame {
+    fn send_all(&self, line: String) {
+        let outboxes = self.seats.iter().map(|seat| &seat.outbox)
+            .chain(self.watchers.iter().map(|(_, outbox)| outbox));
+        for outbox in outboxes {
+            let _ = outbox.send(line.clone());
+        }
+    }
+}
+
+/// Every game on the server, shared between client threads
+#[derive(Default)]
+pub struct Lobby {
+    games: HashMap<u32, HostedGame>,
+    next_id: u32,
+    /// Clients that have said hello
+    greeted: HashSet<u32>
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Carry out one command from `client`, sending replies to `outbox`
+    pub fn handle(&mut self, client: u32, outbox: &Sender<String>, line: &str) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let reply = match words.as_slice() {
+            ["HELLO", version] => match version.parse::<u32>() {
+                Ok(PROTOCOL_VERSION) => {
+                    self.greeted.insert(client);
+                    Ok(format!("WELCOME {}", PROTOCOL_VERSION))
+                },
+                _ => Err(format!("unsupported protocol version {}", version))
+            },
+            _ if !self.greeted.contains(&client) => Err("say HELLO first".to_string()),
+            ["LIST"] => {
+                let mut ids: Vec<&u32> = self.games.keys().collect();
+                ids.sort();
+                Ok(ids.iter().fold("GAMES".to_string(), |list, id| format!("{} {}", list, id)))
+            },
+            ["CREATE", mode, size] => self.create(client, mode, size),
+            ["JOIN", id] => self.join(client, outbox, id),
+            ["WATCH", id] => self.watch(client, outbox, id),
+            ["BOARD", id] => self.find(id).map(|(id, hosted)| board_line(id, &hosted.game)),
+            ["MOVE", id, cell, row, col] => self.make_move(client, id, cell, row, col),
+            _ => Err("unknown command".to_string())
+        };
+        match reply {
+            Ok(line) if line.is_empty() => (),
+            Ok(line) => { let _ = outbox.send(line); },
+            Err(reason) => { let _ = outbox.send(format!("ERROR {}", reason)); }
+        }
+    }
+
+    /// Remove a disconnected client from every game it was seated in or watching
+    ///
+    /// Games the client created that nobody joined are dropped, as are games left with nobody
+    /// seated. A finished game is kept until its watchers have gone too, so they can still look
+    /// at the result.
+    pub fn leave(&mut self, client: u32) {
+        self.greeted.remove(&client);
+        self.games.retain(|id, hosted| {
+            hosted.watchers.retain(|(watcher, _)| *watcher != client);
+            let seated = match hosted.seats.iter().position(|seat| seat.client == client) {
+                Some(index) => {
+                    let seat = hosted.seats.remove(index);
+                    hosted.send_all(format!("LEFT {} {}", id, turn_string(seat.side)));
+                    true
+                },
+                None => false
+            };
+            if !hosted.seats.is_empty() {
+                return true;
+            }
+            if hosted.game.state.is_over() {
+                !hosted.watchers.is_empty()
+            } else {
+                !seated && hosted.creator != client
+            }
+        });
+    }
+
+    fn create(&mut self, client: u32, mode: &str, size: &str) -> Result<String, String> {
+        let mode = parse_mode(mode).ok_or("unknown mode")?;
+        let size = size.parse::<usize>().ok()
+            .filter(|size| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(size))
+            .ok_or(format!("board size must be between {} and {}", MIN_BOARD_SIZE, MAX_BOARD_SIZE))?;
+        let unjoined = self.games.values()
+            .filter(|hosted| hosted.creator == client && hosted.seats.is_empty())
+            .count();
+        if unjoined >= MAX_UNJOINED_GAMES {
+            return Err("too many games waiting for players".to_string());
+        }
+
+        self.next_id += 1;
+        self.games.insert(self.next_id, HostedGame {
+            game: Game::new(mode, size),
+            creator: client,
+            seats: Vec::new(),
+            watchers: Vec::new()
+        });
+        Ok(format!("CREATED {}", self.next_id))
+    }
+
+    fn join(&mut self, client: u32, outbox: &Sender<String>, id: &str) -> Result<String, String> {
+        let (id, hosted) = self.find_mut(id)?;
+        if hosted.seats.iter().any(|seat| seat.client == client) {
+            return Err("already joined".to_string());
+        }
+        // A seat given up by a departed player goes to the next to join
+        let side = [Turn::Left, Turn::Right].into_iter()
+            .find(|side| hosted.seats.iter().all(|seat| seat.side != *side))
+            .ok_or("game is full")?;
+        hosted.seats.push(Seat { client, side, outbox: outbox.clone() });
+        let _ = outbox.send(format!("JOINED {} {}", id, turn_string(side)));
+        let _ = outbox.send(board_line(id, &hosted.game));
+
+        // The game begins once both seats are taken, and a rejoined game carries on
+        if hosted.seats.len() == 2 {
+            if hosted.game.state == State::NotStarted {
+                hosted.game.state = State::Playing;
+            }
+            hosted.send_all(update_line(id, &hosted.game));
+        } else {
+            let _ = outbox.send(update_line(id, &hosted.game));
+        }
+        Ok(String::new())
+    }
+
+    /// Follow a game, first catching up on the moves already played
+    fn watch(&mut self, client: u32, outbox: &Sender<String>, id: &str) -> Result<String, String> {
+        let (id, hosted) = self.find_mut(id)?;
+        if hosted.watchers.iter().any(|(watcher, _)| *watcher == client) {
+            return Err("already watching".to_string());
+        }
+        let _ = outbox.send(format!("WATCHING {}", id));
+        for m in &hosted.game.recording.moves {
+            let _ = outbox.send(format!("MOVE {} {} {} {}", id, cell_string(m.cell), m.row, m.col));
+        }
+        let _ = outbox.send(board_line(id, &hosted.game));
+        let _ = outbox.send(update_line(id, &hosted.game));
+        hosted.watchers.push((client, outbox.clone()));
+        Ok(String::new())
+    }
+
+    fn make_move(&mut self, client: u32, id: &str, cell: &str, row: &str, col: &str) -> Result<String, String> {
+        let (id, hosted) = self.find_mut(id)?;
+        let side = hosted.seats.iter().find(|seat| seat.client == client)
+            .map(|seat| seat.side)
+            .ok_or("not seated in this game")?;
+        if hosted.game.state != State::Playing {
+            return Err("game is not in progress".to_string());
+        }
+        if side != hosted.game.turn {
+            return Err("not your turn".to_string());
+        }
+        let cell = parse_cell(cell).ok_or("letter must be S or O")?;
+        let row = row.parse::<usize>().map_err(|_| "bad row")?;
+        let col = col.parse::<usize>().map_err(|_| "bad column")?;
+
+        let moves_before = hosted.game.recording.moves.len();
+        hosted.game.make_move(cell, row, col);
+        if hosted.game.recording.moves.len() == moves_before {
+            return Err("illegal move".to_string());
+        }
+        hosted.send_all(format!("MOVE {} {} {} {}", id, cell_string(cell), row, col));
+        hosted.send_all(update_line(id, &hosted.game));
+        Ok(String::new())
+    }
+
+    fn find(&self, id: &str) -> Result<(u32, &HostedGame), String> {
+        let id = id.parse::<u32>().map_err(|_| "bad game id")?;
+        self.games.get(&id).map(|hosted| (id, hosted)).ok_or("no such game".to_string())
+    }
+
+    fn find_mut(&mut self, id: &str) -> Result<(u32, &mut HostedGame), String> {
+        let id = id.parse::<u32>().map_err(|_| "bad game id")?;
+        self.games.get_mut(&id).map(|hosted| (id, hosted)).ok_or("no such game".to_string())
+    }
+}
+
+fn board_line(id: u32, game: &Game) -> String {
+    format!("BOARD {} {} {}", id, game.get_board_size(), rows_string(game))
+}
+
+fn update_line(id: u32, game: &Game) -> String {
+    format!("UPDATE {} {} {} {} {}", id, turn_string(game.turn), game.left_score,
+            game.right_score, state_string(game.state))
+}
+
+/// Accepts clients and gives each one a thread
+pub struct Server {
+    lobby: Arc<Mutex<Lobby>>
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self { lobby: Arc::new(Mutex::new(Lobby::new())) }
+    }
+
+    /// Serve clients forever on `listener`
+    pub fn serve(&self, listener: TcpListener) {
+        for (client, stream) in listener.incoming().enumerate() {
+            if let Ok(stream) = stream {
+                let lobby = Arc::clone(&self.lobby);
+                thread::spawn(move || handle_client(stream, lobby, client as u32));
+            }
+        }
+    }
+}
+
+fn handle_client(stream: TcpStream, lobby: Arc<Mutex<Lobby>>, client: u32) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(..) => return
+    };
+    // Lines for this client may come from other clients' threads, so one thread does the writing
+    let (outbox, inbox) = channel::<String>();
+    thread::spawn(move || {
+        for line in inbox {
+            if writer.write_all((line + "\n").as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(..) => break
+        };
+        if line.trim() == "QUIT" {
+            break;
+        }
+        lobby.lock().unwrap().handle(client, &outbox, &line);
+    }
+    lobby.lock().unwrap().leave(client);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use std::time::Duration;
+
+    /// A client that has said hello, with the welcome already read
+    fn client(lobby: &mut Lobby, id: u32) -> (Sender<String>, Receiver<String>) {
+        let (outbox, inbox) = channel();
+        lobby.handle(id, &outbox, "HELLO 1");
+        assert_eq!(drain(&inbox), vec!["WELCOME 1"]);
+        (outbox, inbox)
+    }
+
+    fn drain(inbox: &Receiver<String>) -> Vec<String> {
+        inbox.try_iter().collect()
+    }
+
+    #[test]
+    fn create_assigns_increasing_ids() {
+        let mut lobby = Lobby::new();
+        let (outbox, inbox) = client(&mut lobby, 1);
+        lobby.handle(1, &outbox, "CREATE C 5");
+        lobby.handle(1, &outbox, "CREATE S 3");
+        lobby.handle(1, &outbox, "LIST");
+        assert_eq!(drain(&inbox), vec!["CREATED 1", "CREATED 2", "GAMES 1 2"]);
+    }
+
+    #[test]
+    fn create_rejects_bad_size() {
+        let mut lobby = Lobby::new();
+        let (outbox, inbox) = client(&mut lobby, 1);
+        lobby.handle(1, &outbox, "CREATE C 42");
+        assert!(drain(&inbox)[0].starts_with("ERROR"));
+    }
+
+    #[test]
+    fn game_starts_when_second_player_joins() {
+        let mut lobby = Lobby::new();
+        let (left, left_inbox) = client(&mut lobby, 1);
+        let (right, right_inbox) = client(&mut lobby, 2);
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+
+        assert_eq!(drain(&left_inbox), vec![
+            "CREATED 1", "JOINED 1 L", "BOARD 1 3 ---/---/---", "UPDATE 1 L 0 0 NOTSTARTED", "UPDATE 1 L 0 0 PLAYING"
+        ]);
+        assert_eq!(drain(&right_inbox), vec!["JOINED 1 R", "BOARD 1 3 ---/---/---", "UPDATE 1 L 0 0 PLAYING"]);
+    }
+
+    #[test]
+    fn moves_are_checked_and_broadcast() {
+        let mut lobby = Lobby::new();
+        let (left, left_inbox) = client(&mut lobby, 1);
+        let (right, right_inbox) = client(&mut lobby, 2);
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        drain(&left_inbox);
+        drain(&right_inbox);
+
+        lobby.handle(2, &right, "MOVE 1 S 0 0");
+        assert_eq!(drain(&right_inbox), vec!["ERROR not your turn"]);
+
+        lobby.handle(1, &left, "MOVE 1 S 0 0");
+        assert_eq!(drain(&right_inbox), vec!["MOVE 1 S 0 0", "UPDATE 1 R 0 0 PLAYING"]);
+        lobby.handle(2, &right, "MOVE 1 O 0 0");
+        assert_eq!(drain(&right_inbox), vec!["ERROR illegal move"]);
+        lobby.handle(2, &right, "BOARD 1");
+        assert_eq!(drain(&right_inbox), vec!["BOARD 1 3 S--/---/---"]);
+    }
+
+    #[test]
+    fn leaving_notifies_opponent() {
+        let mut lobby = Lobby::new();
+        let (left, _left_inbox) = client(&mut lobby, 1);
+        let (right, right_inbox) = client(&mut lobby, 2);
+        lobby.handle(1, &left, "CREATE S 4");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        drain(&right_inbox);
+        lobby.leave(1);
+        assert_eq!(drain(&right_inbox), vec!["LEFT 1 L"]);
+    }
+
+    #[test]
+    fn rejoining_takes_the_free_seat_and_carries_on() {
+        let mut lobby = Lobby::new();
+        let (left, _left_inbox) = client(&mut lobby, 1);
+        let (right, _right_inbox) = client(&mut lobby, 2);
+        let (newcomer, newcomer_inbox) = client(&mut lobby, 3);
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        lobby.handle(1, &left, "MOVE 1 S 0 0");
+        lobby.leave(1);
+
+        lobby.handle(3, &newcomer, "JOIN 1");
+        assert_eq!(drain(&newcomer_inbox), vec!["JOINED 1 L", "BOARD 1 3 S--/---/---", "UPDATE 1 R 0 0 PLAYING"]);
+        lobby.handle(4, &newcomer, "HELLO 1");
+        lobby.handle(4, &newcomer, "JOIN 1");
+        assert_eq!(drain(&newcomer_inbox), vec!["WELCOME 1", "ERROR game is full"]);
+    }
+
+    #[test]
+    fn empty_games_are_dropped() {
+        let mut lobby = Lobby::new();
+        let (left, _left_inbox) = client(&mut lobby, 1);
+        let (right, right_inbox) = client(&mut lobby, 2);
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        lobby.handle(1, &left, "JOIN 2");
+        lobby.leave(1);
+        drain(&right_inbox);
+        lobby.handle(2, &right, "LIST");
+        assert_eq!(drain(&right_inbox), vec!["GAMES 1"]);
+    }
+
+    #[test]
+    fn finished_games_stay_until_everyone_has_left() {
+        let mut lobby = Lobby::new();
+        let (left, _left_inbox) = client(&mut lobby, 1);
+        let (right, right_inbox) = client(&mut lobby, 2);
+        let (watcher, watcher_inbox) = client(&mut lobby, 3);
+        lobby.handle(1, &left, "CREATE S 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        lobby.handle(1, &left, "MOVE 1 S 0 0");
+        lobby.handle(2, &right, "MOVE 1 O 0 1");
+        drain(&right_inbox);
+        lobby.handle(1, &left, "MOVE 1 S 0 2");
+        assert_eq!(drain(&right_inbox), vec!["MOVE 1 S 0 2", "UPDATE 1 L 1 0 LEFTWIN"]);
+
+        lobby.handle(3, &watcher, "WATCH 1");
+        lobby.leave(1);
+        lobby.leave(2);
+        drain(&watcher_inbox);
+        lobby.handle(3, &watcher, "BOARD 1");
+        assert_eq!(drain(&watcher_inbox), vec!["BOARD 1 3 SOS/---/---"]);
+        lobby.leave(3);
+        assert!(lobby.games.is_empty());
+    }
+
+    #[test]
+    fn commands_wait_for_hello() {
+        let mut lobby = Lobby::new();
+        let (outbox, inbox) = channel();
+        lobby.handle(1, &outbox, "CREATE C 3");
+        lobby.handle(1, &outbox, "HELLO 99");
+        lobby.handle(1, &outbox, "LIST");
+        assert_eq!(drain(&inbox), vec![
+            "ERROR say HELLO first", "ERROR unsupported protocol version 99", "ERROR say HELLO first"
+        ]);
+        lobby.handle(1, &outbox, "HELLO 1");
+        lobby.handle(1, &outbox, "LIST");
+        assert_eq!(drain(&inbox), vec!["WELCOME 1", "GAMES"]);
+    }
+
+    #[test]
+    fn unjoined_games_go_with_their_creator() {
+        let mut lobby = Lobby::new();
+        let (creator, creator_inbox) = client(&mut lobby, 1);
+        let (other, other_inbox) = client(&mut lobby, 2);
+        for _ in 0..MAX_UNJOINED_GAMES {
+            lobby.handle(1, &creator, "CREATE C 3");
+        }
+        lobby.handle(1, &creator, "CREATE C 3");
+        assert_eq!(drain(&creator_inbox).last().unwrap(), "ERROR too many games waiting for players");
+        lobby.handle(2, &other, "CREATE C 3");
+        lobby.handle(2, &other, "JOIN 1");
+
+        lobby.leave(1);
+        drain(&other_inbox);
+        lobby.handle(2, &other, "LIST");
+        assert_eq!(drain(&other_inbox), vec![format!("GAMES 1 {}", MAX_UNJOINED_GAMES + 1)]);
+    }
+
+    #[test]
+    fn watchers_catch_up_and_follow_moves() {
+        let mut lobby = Lobby::new();
+        let (left, _left_inbox) = client(&mut lobby, 1);
+        let (right, _right_inbox) = client(&mut lobby, 2);
+        let (watcher, watcher_inbox) = client(&mut lobby, 3);
+        lobby.handle(1, &left, "CREATE C 3");
+        lobby.handle(1, &left, "JOIN 1");
+        lobby.handle(2, &right, "JOIN 1");
+        lobby.handle(1, &left, "MOVE 1 S 0 0");
+
+        lobby.handle(3, &watcher, "WATCH 1");
+        assert_eq!(drain(&watcher_inbox), vec![
+            "WATCHING 1", "MOVE 1 S 0 0", "BOARD 1 3 S--/---/---", "UPDATE 1 R 0 0 PLAYING"
+        ]);
+        lobby.handle(3, &watcher, "MOVE 1 O 1 1");
+        assert_eq!(drain(&watcher_inbox), vec!["ERROR not seated in this game"]);
+        lobby.handle(2, &right, "MOVE 1 O 1 1");
+        assert_eq!(drain(&watcher_inbox), vec!["MOVE 1 O 1 1", "UPDATE 1 L 0 0 PLAYING"]);
+
+        lobby.leave(3);
+        lobby.handle(1, &left, "MOVE 1 S 2 2");
+        assert!(drain(&watcher_inbox).is_empty());
+    }
+
+    #[test]
+    fn serves_loopback_clients() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || Server::new().serve(listener));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+
+        stream.write_all(b"HELLO 1\nCREATE C 5\n").unwrap();
+        reader.read_line(&mut line).unwrap();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "WELCOME 1\nCREATED 1\n");
+    }
+}
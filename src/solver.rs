@@ -0,0 +1,298 @@
+//!
+//! Exhaustive solver for small boards
+//!
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::bitboard::MIN_BOARD_SIZE;
+use crate::game::Game;
+use crate::game_enums::{Cell, Mode, State, Turn};
+use crate::recording::Move;
+use crate::symmetry::Symmetry;
+
+/// Largest board that can be solved in reasonable time
+pub const MAX_SOLVABLE_SIZE: usize = 4;
+
+/// Separately locked parts of the table, so threads rarely wait for each other
+const TABLE_SHARDS: usize = 64;
+
+/// Perfect-play search with a transposition table shared between calls
+///
+/// Values are from the point of view of the player on turn. In Classic mode a value is the
+/// difference between the SOSs that player and their opponent will still complete. In Simple
+/// mode it is 1 for a forced win, 0 for a draw and -1 for a forced loss.
+/// Neither depends on the scores so far, so positions are stored by board alone.
+///
+/// Clones share the table, so several threads can solve parts of one game together.
+#[derive(Clone)]
+pub struct Solver {
+    table: Arc<Vec<Mutex<HashMap<u64, i32>>>>,
+    /// Set to abandon a search; nothing found after that is stored
+    stop: Option<Arc<AtomicBool>>
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self { table: Arc::new((0..TABLE_SHARDS).map(|_| Mutex::new(HashMap::new())).collect()), stop: None }
+    }
+
+    /// The same solver, giving up as soon as `stop` is set
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Whether a game is small enough to be solved
+    pub fn can_solve(game: &Game) -> bool {
+        (MIN_BOARD_SIZE..=MAX_SOLVABLE_SIZE).contains(&game.get_board_size())
+    }
+
+    /// Game-theoretic value of the position for the player on turn
+    ///
+    /// Returns None if the board is too large to solve
+    pub fn value(&mut self, game: &Game) -> Option<i32> {
+        if !Self::can_solve(game) {
+            return None;
+        }
+        Some(self.solve(&mut game.clone()))
+    }
+
+    /// Final result of the game if both players play perfectly from here
+    pub fn result(&mut self, game: &Game) -> Option<State> {
+        if game.state != State::Playing {
+            return Some(game.state);
+        }
+        let value = self.value(game)?;
+        let diff = match game.get_mode() {
+            Mode::Classic => game.left_score as i32 - game.right_score as i32 + match game.turn {
+                Turn::Left => value,
+                Turn::Right => -value
+            },
+            Mode::Simple => match game.turn {
+                Turn::Left => value,
+                Turn::Right => -value
+            }
+        };
+        Some(if diff > 0 {
+            State::LeftWin
+        } else if diff < 0 {
+            State::RightWin
+        } else {
+            State::Draw
+        })
+    }
+
+    /// A move that achieves the value of the position
+    pub fn best_move(&mut self, game: &Game) -> Option<Move> {
+        if game.state != State::Playing || !Self::can_solve(game) {
+            return None;
+        }
+        let mut best: Option<(Move, i32)> = None;
+        for m in game.legal_moves() {
+            let value = self.move_value(game, &m);
+            if best.as_ref().is_none_or(|(_, best_value)| value > *best_value) {
+                best = Some((m, value));
+            }
+        }
+        best.map(|(m, _)| m)
+    }
+
+    fn solve(&mut self, game: &mut Game) -> i32 {
+        if game.state != State::Playing || self.stopped() {
+            return 0;
+        }
+        let key = canonical_key(game);
+        if let Some(value) = self.shard(key).lock().unwrap().get(&key) {
+            return *value;
+        }
+
+        let mut best = i32::MIN;
+        for m in moves(game) {
+            best = best.max(self.child_value(game, &m));
+            // Nothing beats winning a Simple game
+            if game.get_mode() == Mode::Simple && best == 1 {
+                break;
+            }
+        }
+        // A value worked out after stopping may be missing moves
+        if !self.stopped() {
+            self.shard(key).lock().unwrap().insert(key, best);
+        }
+        best
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn shard(&self, key: u64) -> &Mutex<HashMap<u64, i32>> {
+        &self.table[((key ^ key >> 29).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as usize % TABLE_SHARDS]
+    }
+
+    /// Value of making `m` for the player making it
+    pub(crate) fn move_value(&mut self, game: &Game, m: &Move) -> i32 {
+        self.child_value(&mut game.clone(), m)
+    }
+
+    /// `move_value`, making and taking back the move on `game` itself
+    fn child_value(&mut self, game: &mut Game, m: &Move) -> i32 {
+        let scores = game.left_score + game.right_score;
+        let played = game.play(m);
+        let made = (game.left_score + game.right_score - scores) as i32;
+
+        let value = if made == 0 {
+            -self.solve(game)
+        } else {
+            match game.get_mode() {
+                // Completing an SOS keeps the turn
+                Mode::Classic => made + self.solve(game),
+                Mode::Simple => 1
+            }
+        };
+        game.unplay(m, played);
+        value
+    }
+}
+
+/// Every empty cell paired with both letters, as `Game::legal_moves` gives them but without
+/// building a list at every position
+fn moves(game: &Game) -> impl Iterator<Item = Move> {
+    let size = game.get_board_size();
+    let mut empty = game.bitboard().empty_mask();
+    std::iter::from_fn(move || {
+        if empty == 0 {
+            return None;
+        }
+        let index = empty.trailing_zeros() as usize;
+        empty &= empty - 1;
+        Some(index)
+    }).flat_map(move |index| [Cell::S, Cell::O].map(|cell| Move { cell, row: index / size, col: index % size }))
+}
+
+/// Canonical board over the 8 symmetries of the square, with the mode and size
+///
+/// The same board as `Bitboard::canonical` picks, found a byte of each mask at a time
+fn canonical_key(game: &Game) -> u64 {
+    let board = game.bitboard();
+    let image = |bytes: &[[u16; 256]; 2], mask: u128| {
+        bytes[0][(mask & 0xff) as usize] as u64 | bytes[1][(mask >> 8 & 0xff) as usize] as u64
+    };
+    let (s, o) = byte_images(board.size()).iter()
+        .map(|bytes| (image(bytes, board.s_mask()), image(bytes, board.o_mask())))
+        .min()
+        .unwrap();
+    let mode_bit = match game.get_mode() {
+        Mode::Classic => 0,
+        Mode::Simple => 1
+    };
+    // Boards up to MAX_SOLVABLE_SIZE have at most 16 cells, so both masks fit below the size
+    s | o << 16 | (board.size() as u64) << 56 | mode_bit << 63
+}
+
+/// Image under each symmetry of every value of the two bytes of a solvable board's mask
+fn byte_images(size: usize) -> &'static [[[u16; 256]; 2]; 8] {
+    static IMAGES: OnceLock<Vec<[[[u16; 256]; 2]; 8]>> = OnceLock::new();
+    let images = IMAGES.get_or_init(|| (0..=MAX_SOLVABLE_SIZE).map(|size| {
+        let mut images = [[[0; 256]; 2]; 8];
+        for (symmetry, bytes) in Symmetry::ALL.iter().zip(images.iter_mut()) {
+            let map = symmetry.cell_map(size);
+            for (byte, table) in bytes.iter_mut().enumerate() {
+                for (value, image) in table.iter_mut().enumerate() {
+                    *image = (0..8).filter(|bit| value >> bit & 1 == 1)
+                        .map(|bit| byte * 8 + bit)
+                        .filter(|index| *index < size * size)
+                        .fold(0, |image, index| image | 1 << map[index]);
+                }
+            }
+        }
+        images
+    }).collect());
+    &images[size]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::Cell;
+
+    fn playing(mode: Mode, size: usize) -> Game {
+        let mut g = Game::new(mode, size);
+        g.state = State::Playing;
+        g
+    }
+
+    #[test]
+    fn refuses_large_boards() {
+        let g = playing(Mode::Classic, 5);
+        assert_eq!(Solver::new().value(&g), None);
+    }
+
+    #[test]
+    fn simple_win_when_sos_available() {
+        let mut g = playing(Mode::Simple, 3);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        let mut solver = Solver::new();
+
+        assert_eq!(solver.value(&g), Some(1));
+        assert_eq!(solver.best_move(&g), Some(Move { cell: Cell::O, row: 0, col: 1 }));
+        assert_eq!(solver.result(&g), Some(State::LeftWin));
+    }
+
+    #[test]
+    fn classic_counts_remaining_sos() {
+        // With S in every corner, Os complete all six SOSs in one chain of extra turns
+        let mut g = playing(Mode::Classic, 3);
+        g.make_move(Cell::S, 0, 0);
+        g.make_move(Cell::S, 0, 2);
+        g.make_move(Cell::S, 2, 0);
+        g.make_move(Cell::S, 2, 2);
+        let mut solver = Solver::new();
+        let m = solver.best_move(&g).unwrap();
+
+        assert_eq!(solver.value(&g), Some(6));
+        assert!(g.sos_if_placed(m.cell, m.row, m.col) > 0);
+    }
+
+    #[test]
+    fn empty_3x3_simple_is_a_draw() {
+        let g = playing(Mode::Simple, 3);
+        assert_eq!(Solver::new().result(&g), Some(State::Draw));
+    }
+
+    #[test]
+    fn symmetric_positions_share_a_key() {
+        let mut a = playing(Mode::Classic, 4);
+        a.make_move(Cell::S, 0, 1);
+        let mut b = playing(Mode::Classic, 4);
+        b.make_move(Cell::S, 2, 3);
+        assert_eq!(canonical_key(&a), canonical_key(&b));
+    }
+
+    #[test]
+    fn keys_hold_the_canonical_board() {
+        for size in 3..=MAX_SOLVABLE_SIZE {
+            let mut g = playing(Mode::Classic, size);
+            for (row, col, cell) in [(0, 1, Cell::S), (2, 0, Cell::O), (1, 2, Cell::S), (2, 2, Cell::O)] {
+                g.make_move(cell, row, col);
+                let (board, _) = g.bitboard().canonical();
+                assert_eq!(canonical_key(&g) & 0xffff_ffff, (board.s_mask() | board.o_mask() << 16) as u64);
+            }
+            assert_eq!(moves(&g).collect::<Vec<Move>>(), g.legal_moves());
+        }
+    }
+
+    #[test]
+    fn modes_do_not_share_keys() {
+        let a = playing(Mode::Classic, 3);
+        let b = playing(Mode::Simple, 3);
+        assert_ne!(canonical_key(&a), canonical_key(&b));
+    }
+}
@@ -0,0 +1,104 @@
+//!
+//! The 8 rotations and reflections of a square board
+//!
+//! Every rule of SOS looks the same after turning or mirroring the board, so positions that are
+//! images of each other have the same value and only one of them needs to be searched or stored.
+//!
+
+use std::sync::OnceLock;
+use crate::bitboard::MAX_BOARD_SIZE;
+use crate::game_enums::Cell;
+use crate::recording::Move;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    /// Quarter turn clockwise
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Mirror left to right
+    FlipHorizontal,
+    /// Mirror top to bottom
+    FlipVertical,
+    /// Mirror in the diagonal from the top left
+    Transpose,
+    /// Mirror in the diagonal from the top right
+    AntiTranspose
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity, Symmetry::Rotate90, Symmetry::Rotate180, Symmetry::Rotate270,
+        Symmetry::FlipHorizontal, Symmetry::FlipVertical, Symmetry::Transpose, Symmetry::AntiTranspose
+    ];
+
+    /// Where the cell at (row, col) of a `size` by `size` board ends up
+    pub fn apply(self, size: usize, row: usize, col: usize) -> (usize, usize) {
+        let last = size - 1;
+        match self {
+            Symmetry::Identity => (row, col),
+            Symmetry::Rotate90 => (col, last - row),
+            Symmetry::Rotate180 => (last - row, last - col),
+            Symmetry::Rotate270 => (last - col, row),
+            Symmetry::FlipHorizontal => (row, last - col),
+            Symmetry::FlipVertical => (last - row, col),
+            Symmetry::Transpose => (col, row),
+            Symmetry::AntiTranspose => (last - col, last - row)
+        }
+    }
+
+    /// The symmetry that undoes this one
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            other => other
+        }
+    }
+
+    /// `m` moved to its image cell; a turn given up keeps its placeholder cell
+    pub fn apply_move(self, size: usize, m: &Move) -> Move {
+        if m.cell == Cell::Empty {
+            return m.clone();
+        }
+        let (row, col) = self.apply(size, m.row, m.col);
+        Move { cell: m.cell, row, col }
+    }
+
+    /// Image of every cell index of a `size` by `size` board, numbered row by row
+    pub(crate) fn cell_map(self, size: usize) -> &'static [usize] {
+        static MAPS: OnceLock<Vec<Vec<Vec<usize>>>> = OnceLock::new();
+        let maps = MAPS.get_or_init(|| (0..=MAX_BOARD_SIZE).map(|size| {
+            Symmetry::ALL.iter().map(|symmetry| (0..size * size).map(|index| {
+                let (row, col) = symmetry.apply(size, index / size, index % size);
+                row * size + col
+            }).collect()).collect()
+        }).collect());
+        &maps[size][self as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inverse_returns_every_cell() {
+        for symmetry in Symmetry::ALL {
+            for (row, col) in [(0, 0), (1, 3), (4, 2)] {
+                let (r, c) = symmetry.apply(5, row, col);
+                assert_eq!(symmetry.inverse().apply(5, r, c), (row, col), "{:?}", symmetry);
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turns_add_up() {
+        let turned = Symmetry::Rotate90.apply(4, 0, 1);
+        assert_eq!(turned, (1, 3));
+        assert_eq!(Symmetry::Rotate90.apply(4, turned.0, turned.1), Symmetry::Rotate180.apply(4, 0, 1));
+        let pass = Move { cell: Cell::Empty, row: 0, col: 0 };
+        assert_eq!(Symmetry::Rotate180.apply_move(4, &pass), pass);
+    }
+}
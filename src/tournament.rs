@@ -0,0 +1,290 @@
+//!
+//! Round-robin and knockout tournaments between computer players
+//!
+
+use crate::book::OpeningBook;
+use crate::game::Game;
+use crate::game_enums::{Mode, State, Turn};
+use crate::player::Player;
+use crate::recording::Recording;
+use crate::solver::Solver;
+
+/// Enumerates how entrants are paired
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format { RoundRobin, Knockout }
+
+/// A named computer player taking part in a tournament
+pub struct Entrant {
+    pub name: String,
+    pub player: Player
+}
+
+/// One finished tournament game, by index into the entrants
+#[derive(Clone, Debug)]
+pub struct TournamentGame {
+    pub round: usize,
+    pub left: usize,
+    pub right: usize,
+    pub state: State,
+    pub left_score: u32,
+    pub right_score: u32,
+    pub recording: Recording
+}
+
+impl TournamentGame {
+    /// Entrant who won, or None for a draw
+    pub fn winner(&self) -> Option<usize> {
+        match self.state.winner()? {
+            Turn::Left => Some(self.left),
+            Turn::Right => Some(self.right)
+        }
+    }
+}
+
+/// One line of the standings table
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Standing {
+    pub name: String,
+    pub played: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub sos_for: u32,
+    pub sos_against: u32
+}
+
+impl Standing {
+    /// Two points for a win and one for a draw
+    pub fn points(&self) -> usize {
+        2 * self.wins + self.draws
+    }
+}
+
+pub struct Tournament {
+    pub format: Format,
+    pub mode: Mode,
+    pub board_size: usize,
+    pub entrants: Vec<Entrant>,
+    pub games: Vec<TournamentGame>,
+    /// Knockout winner, once the tournament has been run
+    pub champion: Option<usize>,
+    /// Consulted by entrants that use an opening book
+    pub book: OpeningBook,
+    solver: Solver
+}
+
+impl Tournament {
+    pub fn new(format: Format, mode: Mode, board_size: usize) -> Self {
+        Self {
+            format,
+            mode,
+            board_size,
+            entrants: Vec::new(),
+            games: Vec::new(),
+            champion: None,
+            book: OpeningBook::default(),
+            solver: Solver::new()
+        }
+    }
+
+    pub fn add_entrant(&mut self, name: &str, player: Player) {
+        self.entrants.push(Entrant { name: name.to_string(), player });
+    }
+
+    /// Play every game of the tournament
+    pub fn run(&mut self) {
+        self.games.clear();
+        self.champion = None;
+        match self.format {
+            Format::RoundRobin => {
+                for (left, right) in round_robin_pairings(self.entrants.len()) {
+                    self.play_game(0, left, right);
+                }
+            },
+            Format::Knockout => self.run_knockout()
+        }
+    }
+
+    /// Entrants ordered by points, then by SOS difference
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.entrants.iter()
+            .map(|entrant| Standing { name: entrant.name.clone(), ..Default::default() })
+            .collect();
+        for game in &self.games {
+            for (index, scored, conceded) in [(game.left, game.left_score, game.right_score),
+                                              (game.right, game.right_score, game.left_score)] {
+                let standing = &mut standings[index];
+                standing.played += 1;
+                standing.sos_for += scored;
+                standing.sos_against += conceded;
+                match game.winner() {
+                    Some(winner) if winner == index => standing.wins += 1,
+                    Some(_) => standing.losses += 1,
+                    None => standing.draws += 1
+                }
+            }
+        }
+        standings.sort_by_key(|s| (std::cmp::Reverse(s.points()),
+                                   std::cmp::Reverse(s.sos_for as i64 - s.sos_against as i64)));
+        standings
+    }
+
+    /// Results of every game followed by the standings table
+    pub fn as_string(&self) -> String {
+        let mut string = String::new();
+        for game in &self.games {
+            string += &format!("Round {}: {} {} - {} {}\n", game.round + 1, self.entrants[game.left].name,
+                               game.left_score, game.right_score, self.entrants[game.right].name);
+        }
+        string += &format!("\n{:<20} {:>3} {:>3} {:>3} {:>3} {:>5} {:>4}\n", "Player", "P", "W", "D", "L", "SOS", "Pts");
+        for s in self.standings() {
+            string += &format!("{:<20} {:>3} {:>3} {:>3} {:>3} {:>2}-{:<2} {:>4}\n", s.name, s.played, s.wins,
+                               s.draws, s.losses, s.sos_for, s.sos_against, s.points());
+        }
+        if let Some(champion) = self.champion {
+            string += &format!("\nWinner: {}\n", self.entrants[champion].name);
+        }
+        string
+    }
+
+    /// Single elimination in entrant order, with a bye for the last entrant of an odd round
+    fn run_knockout(&mut self) {
+        let mut remaining: Vec<usize> = (0..self.entrants.len()).collect();
+        let mut round = 0;
+        while remaining.len() > 1 {
+            let mut next = Vec::new();
+            for pair in remaining.chunks(2) {
+                match *pair {
+                    [a, b] => next.push(self.play_match(round, a, b)),
+                    [bye] => next.push(bye),
+                    _ => ()
+                }
+            }
+            remaining = next;
+            round += 1;
+        }
+        self.champion = remaining.first().copied();
+    }
+
+    /// Play a knockout match and return the entrant going through
+    ///
+    /// The first player alternates by round. A draw is replayed once with sides swapped,
+    /// after which the earlier entrant goes through.
+    fn play_match(&mut self, round: usize, a: usize, b: usize) -> usize {
+        let (left, right) = if round.is_multiple_of(2) { (a, b) } else { (b, a) };
+        let winner = self.play_game(round, left, right).or_else(|| self.play_game(round, right, left));
+        winner.unwrap_or(a)
+    }
+
+    /// Play one game to the end and return the winning entrant, if any
+    fn play_game(&mut self, round: usize, left: usize, right: usize) -> Option<usize> {
+        let mut game = Game::new(self.mode.clone(), self.board_size);
+        game.state = State::Playing;
+        while game.state == State::Playing {
+            let index = match game.turn {
+                Turn::Left => left,
+                Turn::Right => right
+            };
+            let moves_before = game.recording.moves.len();
+            if let Some(m) = self.entrants[index].player.choose_move(&game, &self.solver, &self.book) {
+                game.make_move(m.cell, m.row, m.col);
+            }
+            // Players without a move of their own, or with an illegal one, move randomly
+            if game.recording.moves.len() == moves_before {
+                game.make_random_move();
+            }
+        }
+
+        let result = TournamentGame {
+            round,
+            left,
+            right,
+            state: game.state,
+            left_score: game.left_score,
+            right_score: game.right_score,
+            recording: game.recording
+        };
+        let winner = result.winner();
+        self.games.push(result);
+        winner
+    }
+}
+
+/// Every pair of entrants once, with the first player alternating so each moves first about half the time
+fn round_robin_pairings(entrants: usize) -> Vec<(usize, usize)> {
+    let mut pairings = Vec::new();
+    for a in 0..entrants {
+        for b in a + 1..entrants {
+            pairings.push(if (a + b) % 2 == 1 { (a, b) } else { (b, a) });
+        }
+    }
+    pairings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::{Cell, Strategy};
+
+    fn computer(strategy: Strategy) -> Player {
+        let mut player = Player::new(Cell::S, true);
+        player.strategy = strategy;
+        player
+    }
+
+    fn random_tournament(format: Format, entrants: usize) -> Tournament {
+        let mut tournament = Tournament::new(format, Mode::Simple, 3);
+        for i in 0..entrants {
+            tournament.add_entrant(&format!("Bot {}", i + 1), computer(Strategy::Random));
+        }
+        tournament.run();
+        tournament
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        let pairings = round_robin_pairings(4);
+        assert_eq!(pairings.len(), 6);
+        for player in 0..4 {
+            let first = pairings.iter().filter(|(left, _)| *left == player).count();
+            let second = pairings.iter().filter(|(_, right)| *right == player).count();
+            assert_eq!(first + second, 3);
+            assert!(first >= 1 && second >= 1);
+        }
+    }
+
+    #[test]
+    fn round_robin_records_every_game() {
+        let tournament = random_tournament(Format::RoundRobin, 3);
+        assert_eq!(tournament.games.len(), 3);
+        for game in &tournament.games {
+            assert_ne!(game.state, State::Playing);
+            assert!(!game.recording.moves.is_empty());
+        }
+        let standings = tournament.standings();
+        assert!(standings.iter().all(|s| s.played == 2));
+        assert_eq!(standings.iter().map(|s| s.points()).sum::<usize>(), 6);
+        assert!(standings.windows(2).all(|pair| pair[0].points() >= pair[1].points()));
+    }
+
+    #[test]
+    fn knockout_crowns_a_champion() {
+        let tournament = random_tournament(Format::Knockout, 5);
+        let champion = tournament.champion.unwrap();
+        // The champion never lost a game
+        assert!(tournament.games.iter()
+            .all(|g| g.winner().is_none_or(|w| w == champion) || (g.left != champion && g.right != champion)));
+        assert!(tournament.as_string().contains("Winner: "));
+    }
+
+    #[test]
+    fn perfect_player_is_never_beaten() {
+        let mut tournament = Tournament::new(Format::RoundRobin, Mode::Simple, 3);
+        tournament.add_entrant("Perfect", computer(Strategy::Perfect));
+        tournament.add_entrant("Random", computer(Strategy::Random));
+        tournament.add_entrant("Random 2", computer(Strategy::Random));
+        tournament.run();
+        let perfect = tournament.standings().into_iter().find(|s| s.name == "Perfect").unwrap();
+        assert_eq!(perfect.losses, 0);
+    }
+}
@@ -0,0 +1,116 @@
+//!
+//! Transposition table for remembering searched positions by their Zobrist hash
+//!
+
+use crate::recording::Move;
+
+/// How a stored value relates to the true value of the position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high, so the true value is at least this
+    Lower,
+    /// The search failed low, so the true value is at most this
+    Upper
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Full hash of the position, to tell it apart from others sharing the slot
+    pub hash: u64,
+    /// Letters searched ahead to get the value
+    pub depth: u32,
+    pub value: i32,
+    pub bound: Bound,
+    /// Best move found, worth trying first when the position comes up again
+    pub best: Option<Move>
+}
+
+/// Fixed-size table of search results, one slot per hash with deeper searches kept over shallower ones
+pub struct TranspositionTable {
+    slots: Vec<Option<Entry>>,
+    stored: usize
+}
+
+impl TranspositionTable {
+    /// Table with room for at least `capacity` entries, rounded up to a power of two
+    pub fn new(capacity: usize) -> Self {
+        Self { slots: vec![None; capacity.max(1).next_power_of_two()], stored: 0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of slots in use
+    pub fn len(&self) -> usize {
+        self.stored
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stored == 0
+    }
+
+    /// The entry for the position with `hash`, if one is stored
+    pub fn get(&self, hash: u64) -> Option<&Entry> {
+        self.slots[self.slot(hash)].as_ref().filter(|entry| entry.hash == hash)
+    }
+
+    /// Store a result, unless its slot holds a deeper search of a different position
+    pub fn store(&mut self, entry: Entry) {
+        let slot = self.slot(entry.hash);
+        match &self.slots[slot] {
+            None => self.stored += 1,
+            Some(old) if old.hash != entry.hash && old.depth > entry.depth => return,
+            Some(_) => ()
+        }
+        self.slots[slot] = Some(entry);
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.stored = 0;
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        (hash as usize) & (self.slots.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_enums::Cell;
+
+    fn entry(hash: u64, depth: u32) -> Entry {
+        Entry { hash, depth, value: depth as i32, bound: Bound::Exact, best: Some(Move { cell: Cell::S, row: 0, col: 0 }) }
+    }
+
+    #[test]
+    fn stores_and_finds_entries() {
+        let mut table = TranspositionTable::new(100);
+        assert_eq!(table.capacity(), 128);
+        table.store(entry(5, 2));
+        assert_eq!(table.get(5), Some(&entry(5, 2)));
+        // Same slot, different position
+        assert_eq!(table.get(5 + 128), None);
+        assert_eq!(table.len(), 1);
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.get(5), None);
+    }
+
+    #[test]
+    fn deeper_searches_are_kept() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(3, 4));
+        table.store(entry(3 + 16, 2));
+        assert_eq!(table.get(3), Some(&entry(3, 4)));
+        // A position always replaces its own older result
+        table.store(entry(3, 1));
+        assert_eq!(table.get(3), Some(&entry(3, 1)));
+        table.store(entry(3 + 16, 2));
+        assert_eq!(table.get(3 + 16), Some(&entry(3 + 16, 2)));
+        assert_eq!(table.len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+//!
+//! Zobrist keys for hashing positions
+//!
+//! Every letter in every cell has a random key, and a board hashes to the XOR of the keys of its
+//! letters, so placing a letter updates the hash with a single XOR. The keys come from a fixed
+//! seed, so a position hashes the same way in every run.
+//!
+
+use std::sync::OnceLock;
+use crate::bitboard::{Bitboard, MAX_BOARD_SIZE};
+use crate::game_enums::{Cell, Mode, Turn};
+
+struct Keys {
+    /// One key per cell for an S and one for an O
+    cells: Vec<[u64; 2]>,
+    right_to_move: u64,
+    simple: u64,
+    /// Salt for hashing the score difference
+    score: u64,
+    sizes: Vec<u64>
+}
+
+fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut state = 0x5045_5f53_4f53_u64;
+        let mut next = || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            mix(state)
+        };
+        Keys {
+            cells: (0..MAX_BOARD_SIZE * MAX_BOARD_SIZE).map(|_| [next(), next()]).collect(),
+            right_to_move: next(),
+            simple: next(),
+            score: next(),
+            sizes: (0..=MAX_BOARD_SIZE).map(|_| next()).collect()
+        }
+    })
+}
+
+/// SplitMix64 finalizer, which spreads every input bit over the whole output
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Key for `cell` at the cell numbered `index` row by row, or 0 for an empty cell
+pub fn cell_key(index: usize, cell: Cell) -> u64 {
+    match cell {
+        Cell::S => keys().cells[index][0],
+        Cell::O => keys().cells[index][1],
+        Cell::Empty => 0
+    }
+}
+
+/// XOR of the keys of every letter on `board`
+pub fn board_hash(board: &Bitboard) -> u64 {
+    let mut hash = 0;
+    for (mut mask, letter) in [(board.s_mask(), 0), (board.o_mask(), 1)] {
+        while mask != 0 {
+            hash ^= keys().cells[mask.trailing_zeros() as usize][letter];
+            mask &= mask - 1;
+        }
+    }
+    hash
+}
+
+/// Key for everything about a position besides the board: who moves, the score and the rules
+///
+/// Only the score difference is hashed, since that is all a search cares about
+pub fn state_key(turn: Turn, score_difference: i64, mode: &Mode, board_size: usize) -> u64 {
+    let keys = keys();
+    let mut key = mix(score_difference as u64 ^ keys.score) ^ keys.sizes[board_size];
+    if turn == Turn::Right {
+        key ^= keys.right_to_move;
+    }
+    if matches!(mode, Mode::Simple) {
+        key ^= keys.simple;
+    }
+    key
+}